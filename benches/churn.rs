@@ -0,0 +1,48 @@
+//! Measures how long a future takes to register a file descriptor which is already ready,
+//! be woken, and unregister it, while other file descriptors remain registered.
+//!
+//! The poll backend rebuilds its set of file descriptors whenever registrations change,
+//! whereas the epoll backend only changes the one file descriptor, so compare the two with
+//! `cargo bench --bench churn` and `cargo bench --bench churn --features epoll`. Each idle
+//! registration holds both ends of a pipe open, so that they stay within the usual limit
+//! of 1024 open files.
+
+mod common;
+
+use common::{backend, bench, block_on, noop_waker, pipe};
+use fd_reactor::{FdFuture, Interest, REACTOR};
+use std::{
+    os::unix::io::AsRawFd,
+    sync::{atomic::AtomicI16, Arc},
+};
+
+fn main() {
+    for idle in [0, 100, 400] {
+        let pipes: Vec<_> = (0..idle).map(|_| pipe()).collect();
+        let tokens: Vec<_> = pipes
+            .iter()
+            .map(|(reader, _)| {
+                let fd = reader.as_raw_fd();
+                let token = REACTOR
+                    .register(
+                        fd,
+                        Interest::READ,
+                        Arc::new(AtomicI16::new(0)),
+                        noop_waker(),
+                    )
+                    .unwrap();
+                (fd, token)
+            })
+            .collect();
+
+        let (_reader, writer) = pipe();
+        let name = format!("churn/{}/{} idle fds", backend(), idle);
+        bench(&name, 1000, || {
+            block_on(FdFuture::new(writer.as_raw_fd(), Interest::WRITE));
+        });
+
+        for (fd, token) in tokens {
+            REACTOR.unregister(fd, token);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Helpers which are shared by the benchmarks, which run without a benchmarking harness.
+
+// Not every benchmark uses every helper.
+#![allow(dead_code)]
+
+use std::{
+    future::Future,
+    os::unix::io::{FromRawFd, OwnedFd},
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+/// How many times each benchmark is sampled, of which the median is reported.
+const SAMPLES: usize = 11;
+
+/// Runs the routine for the given number of iterations in each sample, and prints the
+/// median time which an iteration took.
+pub fn bench(name: &str, iterations: u32, mut routine: impl FnMut()) -> Duration {
+    // Warms up caches, and the reactor's allocations, before anything is measured.
+    for _ in 0..iterations {
+        routine();
+    }
+
+    let mut samples: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let started = Instant::now();
+            for _ in 0..iterations {
+                routine();
+            }
+            started.elapsed() / iterations
+        })
+        .collect();
+
+    samples.sort();
+    let median = samples[SAMPLES / 2];
+    println!("{:<48} {:>10.2?}/iter", name, median);
+    median
+}
+
+/// The poller which the benchmarks were compiled to use.
+pub fn backend() -> &'static str {
+    if cfg!(all(target_os = "linux", feature = "epoll")) {
+        "epoll"
+    } else if cfg!(all(not(target_os = "linux"), feature = "kqueue")) {
+        "kqueue"
+    } else {
+        "poll"
+    }
+}
+
+/// Creates a non-blocking pipe, returning its read and write ends.
+pub fn pipe() -> (OwnedFd, OwnedFd) {
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+
+    for &fd in &fds {
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+    }
+
+    unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+}
+
+/// Unparks a thread when woken.
+pub struct Unparker(pub Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Does nothing when woken.
+struct Noop;
+
+impl Wake for Noop {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// A waker which does nothing.
+pub fn noop_waker() -> Waker {
+    Arc::new(Noop).into()
+}
+
+/// Polls the future on the current thread, parking it until the reactor wakes it.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Arc::new(Unparker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+
+        thread::park();
+    }
+}
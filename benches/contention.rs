@@ -0,0 +1,78 @@
+//! Measures the throughput of registering and unregistering file descriptors from many
+//! threads at once, which all contend on the lock of the same reactor's registry.
+//!
+//! Each thread registers and unregisters its own pipe, so that only the lock is shared
+//! between them. Each round releases every thread together, and is timed until the last
+//! of them has finished, so the time per registration is that of a round divided by every
+//! registration which it made. Compare the counts of threads on a machine with at least as
+//! many CPUs, as threads which take turns on fewer CPUs contend less on the lock.
+
+mod common;
+
+use common::{backend, bench, noop_waker, pipe};
+use fd_reactor::{Handle, Interest, Reactor};
+use std::{
+    os::unix::io::{AsRawFd, RawFd},
+    sync::{
+        atomic::{AtomicBool, AtomicI16, Ordering},
+        Arc, Barrier,
+    },
+    thread,
+};
+
+/// How many times each thread registers and unregisters its pipe in each round.
+const REGISTRATIONS: u32 = 500;
+
+fn main() {
+    for threads in [1, 4, 16] {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let barrier = Barrier::new(threads);
+        let stopped = AtomicBool::new(false);
+
+        thread::scope(|scope| {
+            for _ in 1..threads {
+                scope.spawn(|| {
+                    let (reader, _writer) = pipe();
+
+                    loop {
+                        barrier.wait();
+                        if stopped.load(Ordering::SeqCst) {
+                            return;
+                        }
+
+                        churn(&handle, reader.as_raw_fd());
+                        barrier.wait();
+                    }
+                });
+            }
+
+            let (reader, _writer) = pipe();
+            let name = format!("contention/{}/{} threads", backend(), threads);
+            let round = bench(&name, 20, || {
+                barrier.wait();
+                churn(&handle, reader.as_raw_fd());
+                barrier.wait();
+            });
+
+            println!(
+                "{:<48} {:>10.2?}/iter",
+                format!("{}/per registration", name),
+                round / (threads as u32 * REGISTRATIONS)
+            );
+
+            stopped.store(true, Ordering::SeqCst);
+            barrier.wait();
+        });
+    }
+}
+
+/// Registers and unregisters the file descriptor as many times as each round requires.
+fn churn(handle: &Handle, fd: RawFd) {
+    for _ in 0..REGISTRATIONS {
+        let ready = Arc::new(AtomicI16::new(0));
+        let token = handle
+            .register(fd, Interest::READ, ready, noop_waker())
+            .unwrap();
+        handle.unregister(fd, token);
+    }
+}
@@ -0,0 +1,50 @@
+//! Measures the throughput of registering and unregistering a file descriptor.
+//!
+//! The pipe which interrupts the reactor outside of Linux used to be duplicated with
+//! `dup(2)` to signal it from each registration and unregistration. The second benchmark
+//! adds those duplications back to each iteration, and the difference between the two is
+//! what the direct signal saves.
+
+mod common;
+
+use common::{bench, noop_waker, pipe};
+use fd_reactor::{Interest, Reactor};
+use std::{
+    os::unix::io::AsRawFd,
+    sync::{atomic::AtomicI16, Arc},
+};
+
+fn main() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, _writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let register = || {
+        let token = handle
+            .register(
+                fd,
+                Interest::READ,
+                Arc::new(AtomicI16::new(0)),
+                noop_waker(),
+            )
+            .unwrap();
+        handle.unregister(fd, token);
+    };
+
+    let direct = bench("register/signaling the interrupt", 10_000, register);
+
+    let duplicated = bench("register/duplicating the interrupt", 10_000, || {
+        for _ in 0..2 {
+            let duplicate = unsafe { libc::dup(fd) };
+            unsafe { libc::close(duplicate) };
+        }
+
+        register();
+    });
+
+    println!(
+        "{:<48} {:>10.2?}/iter",
+        "register/saved",
+        duplicated.saturating_sub(direct)
+    );
+}
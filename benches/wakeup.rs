@@ -0,0 +1,81 @@
+//! Measures the latency of a wakeup, from a write to a pipe until the thread waiting on its
+//! reader is woken, while other file descriptors remain registered.
+//!
+//! The poller only rebuilds its set of file descriptors once registrations change, which
+//! the second benchmark forces before every wakeup by modifying an idle registration, as
+//! the reactor used to rebuild the set on every iteration. The first benchmark instead
+//! interrupts the reactor by unregistering nothing, so that it is woken before every
+//! wakeup either way, and only the rebuild differs between them.
+
+mod common;
+
+use common::{backend, bench, noop_waker, pipe, Unparker};
+use fd_reactor::{Interest, Mode, Reactor};
+use std::{
+    iter,
+    os::unix::io::AsRawFd,
+    sync::{
+        atomic::{AtomicI16, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+fn main() {
+    for idle in [0, 100, 400] {
+        let (_reactor, handle) = Reactor::new().unwrap();
+
+        let pipes: Vec<_> = (0..idle).map(|_| pipe()).collect();
+        let tokens: Vec<_> = pipes
+            .iter()
+            .map(|(reader, _)| {
+                let ready = Arc::new(AtomicI16::new(0));
+                let token = handle
+                    .register(reader.as_raw_fd(), Interest::READ, ready, noop_waker())
+                    .unwrap();
+                (reader.as_raw_fd(), token)
+            })
+            .collect();
+
+        let (reader, writer) = pipe();
+        let ready = Arc::new(AtomicI16::new(0));
+        let waker = Arc::new(Unparker(thread::current())).into();
+        handle
+            .register_with_mode(
+                reader.as_raw_fd(),
+                Interest::READ,
+                Mode::Edge,
+                ready.clone(),
+                waker,
+            )
+            .unwrap();
+
+        let wakeup = || {
+            let written = unsafe { libc::write(writer.as_raw_fd(), b"0".as_ptr().cast(), 1) };
+            assert_eq!(written, 1);
+
+            while ready.load(Ordering::SeqCst) == 0 {
+                thread::park();
+            }
+
+            let mut byte = 0u8;
+            let read = unsafe { libc::read(reader.as_raw_fd(), (&mut byte as *mut u8).cast(), 1) };
+            assert_eq!(read, 1);
+            ready.store(0, Ordering::SeqCst);
+        };
+
+        let name = format!("wakeup/{}/{} idle fds", backend(), idle);
+        bench(&name, 5000, || {
+            handle.unregister_many(iter::empty());
+            wakeup();
+        });
+
+        if let Some(&(fd, token)) = tokens.first() {
+            let name = format!("wakeup/{}/{} idle fds, rebuilt", backend(), idle);
+            bench(&name, 5000, || {
+                handle.modify_interest(fd, token, Interest::READ);
+                wakeup();
+            });
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Helpers which are shared by the examples, in place of an executor.
+
+// Not every example uses every helper.
+#![allow(dead_code)]
+
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake},
+    thread::{self, Thread},
+};
+
+/// Unparks a thread when woken.
+pub struct Unparker(pub Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls the future on the current thread, parking it until the reactor wakes it.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Arc::new(Unparker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+
+        thread::park();
+    }
+}
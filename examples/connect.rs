@@ -0,0 +1,65 @@
+//! Connects non-blocking sockets to a listener, and to a port which refuses the connection,
+//! waiting on the reactor for each connection to complete.
+
+mod common;
+
+use common::block_on;
+use fd_reactor::connected;
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddrV4, TcpListener},
+    os::unix::io::RawFd,
+};
+
+fn main() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let open = listener.local_addr()?.port();
+
+    // Nothing listens on the port of a listener which was dropped, so its connections are
+    // reset, which the socket reports as an error condition rather than as writability.
+    let closed = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+
+    for port in [open, closed] {
+        let fd = connect(port)?;
+        println!("port {}: {:?}", port, block_on(connected(fd)));
+        unsafe { libc::close(fd) };
+    }
+
+    Ok(())
+}
+
+/// Initiates a non-blocking connection to the port on the loopback address.
+fn connect(port: u16) -> io::Result<RawFd> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let address = SocketAddrV4::new(Ipv4Addr::LOCALHOST, port);
+    let mut sockaddr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    sockaddr.sin_family = libc::AF_INET as libc::sa_family_t;
+    sockaddr.sin_port = address.port().to_be();
+    sockaddr.sin_addr.s_addr = u32::from(*address.ip()).to_be();
+
+    let returned = unsafe {
+        libc::connect(
+            fd,
+            &sockaddr as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    };
+
+    // The connection may complete, or be refused, before `connect` even returns.
+    match io::Error::last_os_error() {
+        _ if returned == 0 => Ok(fd),
+        why if why.raw_os_error() == Some(libc::EINPROGRESS) => Ok(fd),
+        why => {
+            unsafe { libc::close(fd) };
+            Err(why)
+        }
+    }
+}
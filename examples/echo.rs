@@ -0,0 +1,57 @@
+//! Echoes bytes received on a non-blocking `UnixStream` back to the peer.
+
+mod common;
+
+use common::block_on;
+use fd_reactor::AsyncFd;
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    thread,
+};
+
+fn main() -> io::Result<()> {
+    let (stream, mut peer) = UnixStream::pair()?;
+    stream.set_nonblocking(true)?;
+
+    let client = thread::spawn(move || -> io::Result<()> {
+        for message in &["hello", "world"] {
+            peer.write_all(message.as_bytes())?;
+
+            let mut buffer = [0u8; 5];
+            peer.read_exact(&mut buffer)?;
+            println!("echoed: {}", String::from_utf8_lossy(&buffer));
+        }
+
+        Ok(())
+    });
+
+    block_on(echo(AsyncFd::new(stream)))?;
+    client.join().unwrap()
+}
+
+async fn echo(stream: AsyncFd<UnixStream>) -> io::Result<()> {
+    let mut buffer = [0u8; 1024];
+
+    loop {
+        let mut guard = stream.readable().await;
+
+        let read = match guard.try_io(|mut stream| stream.read(&mut buffer)) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(why) if why.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(why) => return Err(why),
+        };
+
+        let mut written = 0;
+        while written < read {
+            let mut guard = stream.writable().await;
+
+            match guard.try_io(|mut stream| stream.write(&buffer[written..read])) {
+                Ok(count) => written += count,
+                Err(why) if why.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(why) => return Err(why),
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Writes more bytes into a non-blocking pipe than it can hold, waiting for the pipe to
+//! become writable again whenever it fills up, while another thread drains it.
+
+mod common;
+
+use common::block_on;
+use fd_reactor::AsyncFd;
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::unix::io::{FromRawFd, RawFd},
+    thread,
+    time::Duration,
+};
+
+const TOTAL: usize = 1024 * 1024;
+
+fn main() -> io::Result<()> {
+    let mut fds: [RawFd; 2] = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // The pipe's file descriptors are owned by the files from here on.
+    let (mut reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    if unsafe { libc::fcntl(fds[1], libc::F_SETFL, libc::O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let drainer = thread::spawn(move || -> io::Result<usize> {
+        // Leaves the writer waiting on a full pipe before draining it.
+        thread::sleep(Duration::from_millis(100));
+
+        let mut buffer = [0u8; 4096];
+        let mut received = 0;
+        loop {
+            match reader.read(&mut buffer)? {
+                0 => return Ok(received),
+                read => received += read,
+            }
+        }
+    });
+
+    let (written, waits) = block_on(fill(AsyncFd::new(writer)))?;
+    println!(
+        "wrote {} bytes, waiting for writability {} times",
+        written, waits
+    );
+    println!("received {} bytes", drainer.join().unwrap()?);
+    Ok(())
+}
+
+/// Writes every byte into the pipe, returning how many were written, and how many times the
+/// pipe was full.
+async fn fill(pipe: AsyncFd<File>) -> io::Result<(usize, usize)> {
+    let buffer = [0u8; 4096];
+    let (mut written, mut waits) = (0, 0);
+
+    while written < TOTAL {
+        let end = TOTAL.min(written + buffer.len());
+        let mut guard = pipe.writable().await;
+
+        // A full pipe is reported writable again once the reader has made room for at
+        // least `PIPE_BUF` bytes, which a write of that many may then fill.
+        match guard.try_io(|mut pipe| pipe.write(&buffer[..end - written])) {
+            Ok(count) => written += count,
+            Err(why) if why.kind() == io::ErrorKind::WouldBlock => waits += 1,
+            Err(why) => return Err(why),
+        }
+    }
+
+    // Dropping the writer lets the reader observe the end of the pipe.
+    Ok((written, waits))
+}
@@ -0,0 +1,59 @@
+//! Waits on the reactor for connections to a non-blocking `TcpListener`.
+
+mod common;
+
+use common::Unparker;
+use fd_reactor::{Interest, REACTOR};
+use std::{
+    io::{self, Write},
+    net::{TcpListener, TcpStream},
+    os::unix::io::AsRawFd,
+    sync::{
+        atomic::{AtomicI16, Ordering},
+        Arc,
+    },
+    task::Waker,
+    thread,
+};
+
+fn main() -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+
+    let address = listener.local_addr()?;
+    let client = thread::spawn(move || -> io::Result<()> {
+        for _ in 0..2 {
+            TcpStream::connect(address)?.write_all(b"hello")?;
+        }
+
+        Ok(())
+    });
+
+    let mut accepted = 0;
+    while accepted < 2 {
+        match listener.accept() {
+            Ok((_, peer)) => {
+                println!("accepted: {}", peer);
+                accepted += 1;
+            }
+            Err(why) if why.kind() == io::ErrorKind::WouldBlock => wait_readable(&listener)?,
+            Err(why) => return Err(why),
+        }
+    }
+
+    client.join().unwrap()
+}
+
+/// Parks the current thread until the reactor finds the listener readable.
+fn wait_readable(listener: &TcpListener) -> io::Result<()> {
+    let ready = Arc::new(AtomicI16::new(0));
+    let waker = Waker::from(Arc::new(Unparker(thread::current())));
+    let token = REACTOR.register_source(listener, Interest::READ, ready.clone(), waker)?;
+
+    while ready.load(Ordering::SeqCst) == 0 {
+        thread::park();
+    }
+
+    REACTOR.unregister(listener.as_raw_fd(), token);
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//! Copies bytes between the ends of a `UnixStream` pair within a tokio runtime, while
+//! waiting on this reactor rather than on tokio's.
+
+use fd_reactor::TokioFd;
+use std::{io, os::unix::net::UnixStream};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> io::Result<()> {
+    let (reader, writer) = UnixStream::pair()?;
+    reader.set_nonblocking(true)?;
+    writer.set_nonblocking(true)?;
+
+    let (mut reader, mut writer) = (TokioFd::new(reader), TokioFd::new(writer));
+
+    let write = tokio::spawn(async move {
+        writer.write_all(b"hello from tokio").await?;
+        writer.shutdown().await?;
+        drop(writer);
+        Ok::<_, io::Error>(())
+    });
+
+    let mut message = String::new();
+    reader.read_to_string(&mut message).await?;
+    println!("received: {}", message);
+
+    write.await.unwrap()
+}
@@ -0,0 +1,257 @@
+use crate::{FdFuture, Interest, ReadyEvents};
+use std::{
+    io,
+    os::unix::io::AsRawFd,
+    sync::atomic::{AtomicI16, Ordering},
+};
+
+/// Owns a file descriptor, and waits on the global reactor for it to become ready.
+///
+/// The readiness observed by the reactor is cached until a [`ReadyGuard`] clears it, which
+/// callers should do once an operation on the file descriptor fails with `WouldBlock`. The
+/// file descriptor is only registered onto the reactor while waiting for readiness, so it
+/// is never left registered once the `AsyncFd` is dropped.
+pub struct AsyncFd<T: AsRawFd> {
+    inner: T,
+
+    /// Events which the reactor reported, and which have not yet been cleared.
+    ready: AtomicI16,
+}
+
+impl<T: AsRawFd> AsyncFd<T> {
+    /// Wraps a file descriptor which has been set to non-blocking mode.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            ready: AtomicI16::new(0),
+        }
+    }
+
+    /// A reference to the wrapped value.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// A mutable reference to the wrapped value.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps the inner value.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Waits until the file descriptor is readable.
+    pub async fn readable(&self) -> ReadyGuard<'_, T> {
+        self.ready(Interest::READ).await
+    }
+
+    /// Waits until the file descriptor is writable.
+    pub async fn writable(&self) -> ReadyGuard<'_, T> {
+        self.ready(Interest::WRITE).await
+    }
+
+    /// Waits until any of the events of the given interest occur on the file descriptor.
+    pub async fn ready(&self, interest: Interest) -> ReadyGuard<'_, T> {
+        let mut events = self.cached(interest);
+
+        if events.is_empty() {
+            events = FdFuture::new(self.inner.as_raw_fd(), interest).await;
+            self.ready.fetch_or(events.bits(), Ordering::SeqCst);
+        }
+
+        ReadyGuard {
+            async_fd: self,
+            interest,
+            events,
+        }
+    }
+
+    /// Cached events which are relevant to the given interest.
+    fn cached(&self, interest: Interest) -> ReadyEvents {
+        let events = ReadyEvents::from_bits_truncate(self.ready.load(Ordering::SeqCst));
+        events & (interest | Interest::UNMASKABLE)
+    }
+}
+
+/// Proof that the file descriptor of an [`AsyncFd`] was reported to be ready.
+pub struct ReadyGuard<'a, T: AsRawFd> {
+    async_fd: &'a AsyncFd<T>,
+    interest: Interest,
+    events: ReadyEvents,
+}
+
+impl<'a, T: AsRawFd> ReadyGuard<'a, T> {
+    /// The events which were observed on the file descriptor.
+    pub fn ready(&self) -> ReadyEvents {
+        self.events
+    }
+
+    /// A reference to the value wrapped by the `AsyncFd`.
+    pub fn get_ref(&self) -> &'a T {
+        &self.async_fd.inner
+    }
+
+    /// Clears the cached readiness, so that the next wait registers onto the reactor again.
+    ///
+    /// An error condition is cleared along with it, as a socket's error is cleared once it
+    /// has been read, whereas a hangup, or the file descriptor having been closed, persists,
+    /// and remains cached so that every later wait resolves immediately with it.
+    pub fn clear_ready(&mut self) {
+        let cleared = self.interest | Interest::ERROR;
+        self.async_fd
+            .ready
+            .fetch_and(!cleared.bits(), Ordering::SeqCst);
+    }
+
+    /// Clears the cached readiness, and waits for the file descriptor to become ready
+    /// again with the same interest.
+    ///
+    /// This is for loops which drain the file descriptor until it would block. The clear
+    /// and the registration are separate steps, rather than one made under the reactor's
+    /// lock, yet readiness which arrives between them is not lost: the reactor reports the
+    /// readiness which a file descriptor already has once it is registered again, rather
+    /// than only changes to it, so the wait then resolves without further events.
+    pub async fn clear_ready_and_rearm(mut self) -> ReadyGuard<'a, T> {
+        self.clear_ready();
+        self.async_fd.ready(self.interest).await
+    }
+
+    /// Performs an operation on the file descriptor, clearing the cached readiness if
+    /// the operation fails with `WouldBlock`.
+    pub fn try_io<R>(&mut self, f: impl FnOnce(&'a T) -> io::Result<R>) -> io::Result<R> {
+        let result = f(self.get_ref());
+
+        if let Err(why) = &result {
+            if why.kind() == io::ErrorKind::WouldBlock {
+                self.clear_ready();
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, REACTOR};
+    use std::{
+        future::Future,
+        os::unix::io::OwnedFd,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Waker},
+    };
+
+    /// Readiness is cached until an operation would block, after which the next wait
+    /// registers onto the reactor again, and resolves once the file descriptor is ready.
+    #[test]
+    fn readiness_is_cached_until_operations_would_block() {
+        let (reader, writer) = pipe();
+        let fd = reader.as_raw_fd();
+        let reader = AsyncFd::new(reader);
+        write_byte(&writer);
+
+        let mut guard = block_on(reader.readable());
+        assert_eq!(guard.ready(), Interest::READ);
+        guard.try_io(read_byte).unwrap();
+
+        // Drained, though still cached, so the wait resolves without the reactor.
+        let mut guard = block_on(reader.readable());
+        let why = guard.try_io(read_byte).unwrap_err();
+        assert_eq!(why.kind(), io::ErrorKind::WouldBlock);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut readable = pin!(reader.readable());
+        assert!(readable.as_mut().poll(&mut cx).is_pending());
+        assert!(REACTOR.is_registered(fd));
+
+        write_byte(&writer);
+        assert_eq!(block_on(readable).ready(), Interest::READ);
+    }
+
+    /// Rearming observes readiness which arrived after the file descriptor was drained, and
+    /// is otherwise woken by readiness which arrives while it waits.
+    #[test]
+    fn rearming_observes_readiness_from_before_and_after() {
+        let (reader, writer) = pipe();
+        let reader = AsyncFd::new(reader);
+        write_byte(&writer);
+
+        let mut guard = block_on(reader.readable());
+        guard.try_io(read_byte).unwrap();
+        write_byte(&writer);
+        let mut guard = block_on(guard.clear_ready_and_rearm());
+        assert_eq!(guard.ready(), Interest::READ);
+        guard.try_io(read_byte).unwrap();
+
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut rearmed = pin!(guard.clear_ready_and_rearm());
+        assert!(rearmed.as_mut().poll(&mut cx).is_pending());
+
+        write_byte(&writer);
+        wait_until(|| counter.0.load(Ordering::SeqCst) != 0);
+        match rearmed.as_mut().poll(&mut cx) {
+            Poll::Ready(guard) => assert_eq!(guard.ready(), Interest::READ),
+            Poll::Pending => panic!("rearming was woken without resolving"),
+        }
+    }
+
+    /// Writers which fill the pipe wait until its reader has drained it, and are then woken
+    /// as writable.
+    #[test]
+    fn full_pipes_wake_writers_once_drained() {
+        let (reader, writer) = pipe();
+        let writer = AsyncFd::new(writer);
+        let write = |fd: &OwnedFd| {
+            let written = unsafe { libc::write(fd.as_raw_fd(), [0u8; 4096].as_ptr().cast(), 4096) };
+            match written {
+                -1 => Err(io::Error::last_os_error()),
+                written => Ok(written as usize),
+            }
+        };
+
+        loop {
+            let mut guard = block_on(writer.writable());
+            match guard.try_io(write) {
+                Ok(_) => (),
+                Err(why) if why.kind() == io::ErrorKind::WouldBlock => break,
+                Err(why) => panic!("write: {}", why),
+            }
+        }
+
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut writable = pin!(writer.writable());
+        assert!(writable.as_mut().poll(&mut cx).is_pending());
+
+        let mut buffer = [0u8; 4096];
+        while unsafe { libc::read(reader.as_raw_fd(), buffer.as_mut_ptr().cast(), 4096) } > 0 {}
+        wait_until(|| counter.0.load(Ordering::SeqCst) != 0);
+        match writable.as_mut().poll(&mut cx) {
+            Poll::Ready(guard) => assert_eq!(guard.ready(), Interest::WRITE),
+            Poll::Pending => panic!("the writer was woken without resolving"),
+        }
+    }
+
+    /// Error conditions are cleared along with the interest, whereas hangups remain cached.
+    #[test]
+    fn only_hangups_outlive_clearing() {
+        let (reader, _writer) = pipe();
+        let reader = AsyncFd::new(reader);
+        let cached = Interest::READ | Interest::ERROR | Interest::HANGUP;
+        reader.ready.store(cached.bits(), Ordering::SeqCst);
+
+        let mut guard = block_on(reader.readable());
+        assert_eq!(guard.ready(), cached);
+        guard.clear_ready();
+        assert_eq!(reader.cached(Interest::READ), Interest::HANGUP);
+        assert_eq!(block_on(reader.writable()).ready(), Interest::HANGUP);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::Interest;
+use std::sync::{
+    atomic::{AtomicBool, AtomicI16, Ordering},
+    Arc,
+};
+
+/// Receives the events which the reactor observes for a registration.
+///
+/// The reactor signals the completion before waking the registration's waker, from its
+/// background thread, and while holding the lock of its registry. Implementations should
+/// therefore only record the events, and must not call back into the reactor.
+pub trait Completion: Send + Sync {
+    /// Records the events which were observed on the file descriptor.
+    fn signal(&self, events: Interest);
+
+    /// The events which have been signaled and not yet cleared by the registration.
+    ///
+    /// Edge-triggered registrations are only woken by events which are not already pending.
+    /// By default, no events are considered pending, so every signal wakes the registration.
+    fn pending(&self) -> Interest {
+        Interest::empty()
+    }
+
+    /// Discards the pending events which are not among the given events.
+    ///
+    /// The reactor calls this when the interest of the registration is modified, so that
+    /// events which it is no longer interested in do not complete it. By default, nothing
+    /// is discarded.
+    fn retain(&self, _events: Interest) {}
+}
+
+/// Stores the bits of the observed events, which are pending until cleared to `0`.
+impl Completion for AtomicI16 {
+    fn signal(&self, events: Interest) {
+        self.fetch_or(events.bits(), Ordering::SeqCst);
+    }
+
+    fn pending(&self) -> Interest {
+        Interest::from_bits_truncate(self.load(Ordering::SeqCst))
+    }
+
+    fn retain(&self, events: Interest) {
+        self.fetch_and(events.bits(), Ordering::SeqCst);
+    }
+}
+
+/// Stores `true` once any events are observed, which are pending until cleared to `false`.
+///
+/// Which events were observed is not recorded, so none are discarded when the interest of
+/// the registration is modified.
+impl Completion for AtomicBool {
+    fn signal(&self, _events: Interest) {
+        self.store(true, Ordering::SeqCst);
+    }
+
+    fn pending(&self) -> Interest {
+        if self.load(Ordering::SeqCst) {
+            Interest::all()
+        } else {
+            Interest::empty()
+        }
+    }
+}
+
+impl<C: Completion + ?Sized> Completion for Arc<C> {
+    fn signal(&self, events: Interest) {
+        (**self).signal(events);
+    }
+
+    fn pending(&self) -> Interest {
+        (**self).pending()
+    }
+
+    fn retain(&self, events: Interest) {
+        (**self).retain(events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, Mode, Reactor};
+    use std::{os::unix::io::AsRawFd, sync::Mutex};
+
+    /// Records every signal, and every event which was discarded.
+    #[derive(Default)]
+    struct Recorder {
+        signaled: Mutex<Vec<Interest>>,
+        retained: Mutex<Vec<Interest>>,
+    }
+
+    impl Completion for Recorder {
+        fn signal(&self, events: Interest) {
+            self.signaled.lock().unwrap().push(events);
+        }
+
+        fn retain(&self, events: Interest) {
+            self.retained.lock().unwrap().push(events);
+        }
+    }
+
+    /// Custom completions are signaled with the events which were observed, and told which
+    /// events to retain when their interest is modified, which include the conditions that
+    /// are reported regardless of interest.
+    #[test]
+    fn custom_completions_record_their_events() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (socket, peer) = socketpair();
+        let fd = socket.as_raw_fd();
+
+        // Suspended until its interest is modified, so that nothing is signaled before.
+        let recorder = Arc::new(Recorder::default());
+        let (mode, waker) = (Mode::OneShot, noop_waker());
+        let token = handle
+            .register_with_mode(fd, Interest::empty(), mode, recorder.clone(), waker)
+            .unwrap();
+
+        handle.modify_interest(fd, token, Interest::READ);
+        let retained = Interest::READ | Interest::UNMASKABLE | Interest::TIMEOUT;
+        assert_eq!(*recorder.retained.lock().unwrap(), [retained]);
+        assert!(recorder.signaled.lock().unwrap().is_empty());
+
+        write_byte(&peer);
+        wait_until(|| !handle.is_registered(fd));
+        assert_eq!(*recorder.signaled.lock().unwrap(), [Interest::READ]);
+    }
+}
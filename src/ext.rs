@@ -0,0 +1,144 @@
+//! Convenience constructors for futures which wait on the global reactor.
+
+use crate::{FdFuture, FdTimeoutFuture, Interest};
+use std::{io, os::unix::io::RawFd, time::Duration};
+
+/// Waits until the file descriptor can be read from without blocking.
+///
+/// ```
+/// use fd_reactor::readable;
+/// # use std::{future::Future, pin::pin, sync::Arc, task::{Context, Poll, Wake}, thread};
+/// #
+/// # /// Polls the future on the current thread, parking it until the reactor wakes it.
+/// # fn block_on<F: Future>(future: F) -> F::Output {
+/// #     struct Unparker(thread::Thread);
+/// #
+/// #     impl Wake for Unparker {
+/// #         fn wake(self: Arc<Self>) {
+/// #             self.0.unpark();
+/// #         }
+/// #     }
+/// #
+/// #     let waker = Arc::new(Unparker(thread::current())).into();
+/// #     let mut cx = Context::from_waker(&waker);
+/// #     let mut future = pin!(future);
+/// #
+/// #     loop {
+/// #         match future.as_mut().poll(&mut cx) {
+/// #             Poll::Ready(output) => return output,
+/// #             Poll::Pending => thread::park(),
+/// #         }
+/// #     }
+/// # }
+///
+/// let mut fds = [0; 2];
+/// assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+///
+/// unsafe { libc::write(fds[1], b"0".as_ptr() as *const libc::c_void, 1) };
+/// let events = block_on(readable(fds[0]));
+/// assert!(events.is_readable());
+///
+/// unsafe {
+///     libc::close(fds[0]);
+///     libc::close(fds[1]);
+/// }
+/// ```
+pub fn readable(fd: RawFd) -> FdFuture<'static> {
+    ready(fd, Interest::READ)
+}
+
+/// Waits until the file descriptor can be written to without blocking.
+pub fn writable(fd: RawFd) -> FdFuture<'static> {
+    ready(fd, Interest::WRITE)
+}
+
+/// Waits until the file descriptor can be read from without blocking, or until the timeout
+/// elapses.
+pub fn readable_timeout(fd: RawFd, timeout: Duration) -> FdTimeoutFuture<'static> {
+    FdFuture::with_timeout(fd, Interest::READ, timeout)
+}
+
+/// Waits until the file descriptor can be written to without blocking, or until the timeout
+/// elapses.
+///
+/// This bounds how long a writer waits for the backpressure of a full pipe or socket to
+/// clear, resolving to [`Readiness::TimedOut`](crate::Readiness::TimedOut) if it does not.
+pub fn writable_timeout(fd: RawFd, timeout: Duration) -> FdTimeoutFuture<'static> {
+    FdFuture::with_timeout(fd, Interest::WRITE, timeout)
+}
+
+/// Waits until any of the events of the given interest occur on the file descriptor.
+pub fn ready(fd: RawFd, interest: Interest) -> FdFuture<'static> {
+    FdFuture::new(fd, interest)
+}
+
+/// Waits for a non-blocking `connect(2)` which was initiated on the socket to complete.
+///
+/// A non-blocking connect fails with `EINPROGRESS`, and completes once the socket becomes
+/// writable, after which its pending `SO_ERROR` is the outcome of the connection. This
+/// resolves to that error, such as [`io::ErrorKind::ConnectionRefused`], or to `Ok` once
+/// the socket is connected.
+///
+/// A refused connection may be reported only as an error condition, without writability.
+/// [`Interest::ERROR`] is always reported, so it wakes the future even though it only
+/// waits for the socket to become writable, and the error is then taken from `SO_ERROR`
+/// rather than from the events.
+pub async fn connected(fd: RawFd) -> io::Result<()> {
+    writable(fd).try_ready().await?;
+
+    match crate::sys::socket_error(fd) {
+        Some(why) => Err(why),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::Readiness;
+    use std::{net::TcpListener, os::unix::io::AsRawFd, time::Instant};
+
+    #[test]
+    fn connections_complete() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket = connect(listener.local_addr().unwrap().port());
+
+        block_on(connected(socket.as_raw_fd())).unwrap();
+    }
+
+    /// Nothing listens on the port of a listener which was dropped, so the connection is reset,
+    /// and the refusal is taken from the socket's pending error.
+    #[test]
+    fn refused_connections_fail() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .port();
+        let socket = connect(port);
+
+        let why = block_on(connected(socket.as_raw_fd())).unwrap_err();
+        assert_eq!(why.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(why.raw_os_error(), Some(libc::ECONNREFUSED));
+    }
+
+    /// Writers resolve with the writable event while there is room in the pipe, and time out
+    /// once it is full and nothing drains it.
+    #[test]
+    fn writers_resolve_or_time_out() {
+        let (_reader, writer) = pipe();
+        let fd = writer.as_raw_fd();
+
+        let ready = block_on(writable_timeout(fd, TIMEOUT));
+        assert_eq!(ready, Readiness::Ready(Interest::WRITE));
+
+        // The reactor may otherwise still report the writability which it observed before
+        // the pipe was filled.
+        crate::REACTOR.sync();
+        while unsafe { libc::write(fd, [0u8; 4096].as_ptr().cast(), 4096) } > 0 {}
+        let timeout = Duration::from_millis(20);
+        let started = Instant::now();
+        assert_eq!(block_on(writable_timeout(fd, timeout)), Readiness::TimedOut);
+        assert!(started.elapsed() >= timeout);
+    }
+}
@@ -0,0 +1,815 @@
+use crate::{
+    reactor::{clone_error, Registration},
+    Completion, Handle, Interest, ReadyEvents, Token, REACTOR,
+};
+use std::{
+    future::Future,
+    io,
+    marker::PhantomPinned,
+    os::unix::io::{AsFd, AsRawFd, OwnedFd, RawFd},
+    pin::Pin,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicI16, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// A future which resolves when the reactor reports that the file descriptor is ready.
+///
+/// The file descriptor is registered onto the reactor when first polled, and is
+/// unregistered either on completion, or when the future is dropped.
+///
+/// The future resolves to the events which were observed, which may simply be ignored by
+/// callers that only need to know that the file descriptor is ready. A file descriptor
+/// which the reactor refuses to register resolves immediately with [`Interest::INVALID`],
+/// and the error which the registration failed with is returned by
+/// [`FdFuture::try_ready`].
+///
+/// The future is one-shot: once it has resolved, polling it again resolves immediately with
+/// the same events. To wait on the file descriptor again with the same future, such as after
+/// draining it until it would block, call [`FdFuture::reset`].
+///
+/// The reactor keeps no readiness of its own for file descriptors by default, as it has no
+/// way of knowing when a caller has drained one. Callers which wait on the same file
+/// descriptor repeatedly should use an [`AsyncFd`](crate::AsyncFd), which caches the
+/// readiness that the reactor reported until an operation would block, and only registers
+/// onto the reactor once it has been cleared. A reactor which is built with a
+/// [readiness cache](crate::ReactorBuilder::readiness_cache) resolves futures with the
+/// readiness that it cached instead of registering them.
+pub struct FdFuture<'a> {
+    /// The file descriptor which is waited on, and its registration.
+    waiting: Waiting<'a>,
+
+    /// Set by the reactor to the observed events when the file descriptor is ready.
+    ready: Arc<AtomicI16>,
+
+    /// When the reactor should give up waiting on the file descriptor.
+    deadline: Option<Instant>,
+}
+
+impl FdFuture<'static> {
+    /// Creates a future which waits for the given interest on the file descriptor.
+    pub fn new(fd: RawFd, interest: Interest) -> Self {
+        Self::new_with(&REACTOR, fd, interest)
+    }
+
+    /// Creates a future which gives up waiting on the file descriptor after a timeout.
+    pub fn with_timeout(
+        fd: RawFd,
+        interest: Interest,
+        timeout: Duration,
+    ) -> FdTimeoutFuture<'static> {
+        let mut future = Self::new(fd, interest);
+        future.deadline = Some(Instant::now() + timeout);
+        FdTimeoutFuture { future }
+    }
+}
+
+impl<'a> FdFuture<'a> {
+    /// Creates a future which borrows the file descriptor for as long as it is registered.
+    ///
+    /// Unlike [`FdFuture::new`], this guarantees that the file descriptor can not be closed
+    /// while the reactor is polling it, as the future may not outlive its owner:
+    ///
+    /// ```compile_fail,E0515
+    /// use fd_reactor::{FdFuture, Interest};
+    /// use std::os::unix::io::OwnedFd;
+    ///
+    /// fn readable(fd: OwnedFd) -> FdFuture<'static> {
+    ///     // `fd` is closed once this returns, while the future still borrows it.
+    ///     FdFuture::from_fd(&fd, Interest::READ)
+    /// }
+    /// ```
+    pub fn from_fd<F: AsFd + ?Sized>(fd: &'a F, interest: Interest) -> Self {
+        Self::new_with(&REACTOR, fd.as_fd().as_raw_fd(), interest)
+    }
+
+    /// Creates a future which waits on the reactor of the given handle.
+    pub fn new_with(handle: &'a Handle, fd: RawFd, interest: Interest) -> Self {
+        Self {
+            waiting: Waiting::new(handle, fd, interest),
+            ready: Arc::default(),
+            deadline: None,
+        }
+    }
+
+    /// Converts the future into one which resolves to an error if an error condition was
+    /// observed on the file descriptor.
+    pub fn try_ready(self) -> TryFdFuture<'a> {
+        TryFdFuture { future: self }
+    }
+
+    /// Changes the events which the future waits for.
+    ///
+    /// A registration which the reactor already holds is modified in place, rather than
+    /// being registered again. Events which were already observed, but are not among the
+    /// new interest, are discarded, so that they do not complete the future.
+    pub fn set_interest(&mut self, interest: Interest) {
+        self.waiting.interest = interest;
+
+        // Modified immediately, so that the reactor may not signal events of the previous
+        // interest after they have been discarded.
+        if let Some((registration, _, registered)) = self.waiting.registration.as_mut() {
+            if *registered != interest {
+                registration.modify_interest(interest);
+                *registered = interest;
+            }
+        }
+
+        self.ready
+            .retain(interest | Interest::UNMASKABLE | Interest::TIMEOUT);
+    }
+
+    /// Discards the observed events, so that the next poll waits on the reactor again,
+    /// along with the readiness which the reactor cached for the interest, if it does.
+    pub fn reset(&mut self) {
+        // Unregister first, so that the reactor may not store events after they are cleared.
+        self.unregister();
+        self.ready.store(0, Ordering::SeqCst);
+        self.waiting.error = None;
+
+        let waiting = &self.waiting;
+        waiting.handle.clear_readiness(waiting.fd, waiting.interest);
+    }
+
+    /// Removes the file descriptor from the reactor, if it is currently registered.
+    fn unregister(&mut self) {
+        // Dropping the registration unregisters it.
+        self.waiting.registration = None;
+    }
+}
+
+impl<'a> Future for FdFuture<'a> {
+    type Output = ReadyEvents;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let (ready, deadline) = (&this.ready, this.deadline);
+
+        this.waiting
+            .poll(cx, ready, |handle, fd, interest, waker| match deadline {
+                Some(deadline) => {
+                    handle.register_with_deadline(fd, interest, ready.clone(), waker, deadline)
+                }
+                None => handle.register(fd, interest, ready.clone(), waker),
+            })
+    }
+}
+
+impl<'a> Drop for FdFuture<'a> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+/// A future which resolves when the reactor reports that the file descriptor is ready,
+/// storing the observed events within itself rather than in a shared allocation.
+///
+/// This behaves as an [`FdFuture`], but must be pinned to be polled, such as by awaiting it
+/// directly, or with [`std::pin::pin!`]. Use it in place of an `FdFuture` for short-lived
+/// waits, where allocating the shared slot for every future would be wasteful.
+pub struct InlineFdFuture<'a> {
+    /// The file descriptor which is waited on, and its registration.
+    waiting: Waiting<'a>,
+
+    /// Set by the reactor to the observed events when the file descriptor is ready.
+    ///
+    /// The reactor holds a pointer to this slot for as long as it is registered.
+    ready: AtomicI16,
+
+    /// The slot may not move while the reactor holds a pointer to it.
+    _pinned: PhantomPinned,
+}
+
+impl InlineFdFuture<'static> {
+    /// Creates a future which waits for the given interest on the file descriptor.
+    pub fn new(fd: RawFd, interest: Interest) -> Self {
+        Self::new_with(&REACTOR, fd, interest)
+    }
+}
+
+impl<'a> InlineFdFuture<'a> {
+    /// Creates a future which waits on the reactor of the given handle.
+    pub fn new_with(handle: &'a Handle, fd: RawFd, interest: Interest) -> Self {
+        Self {
+            waiting: Waiting::new(handle, fd, interest),
+            ready: AtomicI16::new(0),
+            _pinned: PhantomPinned,
+        }
+    }
+}
+
+impl<'a> Future for InlineFdFuture<'a> {
+    type Output = ReadyEvents;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // Only the registration is replaced through this reference, and the slot is never
+        // moved out of the future.
+        let this = unsafe { self.get_unchecked_mut() };
+        let slot = InlineSlot(NonNull::from(&this.ready));
+
+        this.waiting
+            .poll(cx, &this.ready, |handle, fd, interest, waker| {
+                handle.register(fd, interest, slot, waker)
+            })
+    }
+}
+
+impl<'a> Drop for InlineFdFuture<'a> {
+    fn drop(&mut self) {
+        // The reactor only signals the slot while holding the lock of its registry, which
+        // unregistering takes, even once the registration has been moved onto another
+        // reactor, so the slot is never signaled once this returns.
+        self.waiting.registration = None;
+    }
+}
+
+/// Points the reactor to the slot of an [`InlineFdFuture`].
+///
+/// The future is pinned before it is first registered, so its slot is not moved, and it
+/// remains registered only until it is dropped, which a pinned value must be before its
+/// memory is reused. The pointer is therefore valid whenever the reactor signals it.
+struct InlineSlot(NonNull<AtomicI16>);
+
+// The slot is only ever accessed atomically.
+unsafe impl Send for InlineSlot {}
+unsafe impl Sync for InlineSlot {}
+
+impl Completion for InlineSlot {
+    fn signal(&self, events: Interest) {
+        unsafe { self.0.as_ref() }.signal(events);
+    }
+
+    fn pending(&self) -> Interest {
+        unsafe { self.0.as_ref() }.pending()
+    }
+}
+
+/// The state of a future which waits on the reactor, whichever slot the reactor signals
+/// its events to.
+struct Waiting<'a> {
+    /// The reactor which the file descriptor is registered onto.
+    handle: &'a Handle,
+
+    /// The file descriptor being watched.
+    fd: RawFd,
+
+    /// The events which the reactor should listen for.
+    interest: Interest,
+
+    /// The registration that the reactor currently holds for this future, with the waker
+    /// and interest that it was registered with.
+    registration: Option<(Registration<'a>, Waker, Interest)>,
+
+    /// Why the reactor refused to register the file descriptor, if it did.
+    error: Option<io::Error>,
+}
+
+impl<'a> Waiting<'a> {
+    fn new(handle: &'a Handle, fd: RawFd, interest: Interest) -> Self {
+        Self {
+            handle,
+            fd,
+            interest,
+            registration: None,
+            error: None,
+        }
+    }
+
+    /// Resolves with the events which the reactor signaled to `ready`, or waits for them,
+    /// registering the file descriptor with `register` if the reactor holds no
+    /// registration for the task.
+    fn poll(
+        &mut self,
+        cx: &mut Context,
+        ready: &AtomicI16,
+        register: impl FnOnce(&'a Handle, RawFd, Interest, Waker) -> io::Result<Token>,
+    ) -> Poll<ReadyEvents> {
+        let events = ready.load(Ordering::SeqCst);
+        if events != 0 {
+            self.registration = None;
+            return Poll::Ready(ReadyEvents::from_bits_truncate(events));
+        }
+
+        // The waker and interest of an existing registration are replaced in place if they
+        // changed, so that the file descriptor is polled throughout.
+        let interest = self.interest;
+        if let Some((registration, waker, registered)) = self.registration.as_mut() {
+            if waker.will_wake(cx.waker()) || registration.update_waker(cx.waker().clone()) {
+                waker.clone_from(cx.waker());
+
+                if *registered != interest {
+                    registration.modify_interest(interest);
+                    *registered = interest;
+                }
+
+                return Poll::Pending;
+            }
+        }
+
+        // The reactor only removes registrations of its own accord once it has signaled
+        // them, possibly since the events were last loaded.
+        self.registration = None;
+        let events = ready.load(Ordering::SeqCst);
+        if events != 0 {
+            return Poll::Ready(ReadyEvents::from_bits_truncate(events));
+        }
+
+        // Readiness which the reactor already observed, and which was not cleared since,
+        // resolves the future without registering.
+        let cached = self.handle.cached_readiness(self.fd, interest);
+        if !cached.is_empty() {
+            ready.fetch_or(cached.bits(), Ordering::SeqCst);
+            return Poll::Ready(cached);
+        }
+
+        let waker = cx.waker().clone();
+        match register(self.handle, self.fd, interest, waker.clone()) {
+            Ok(token) => {
+                let registration = Registration::new(self.handle, self.fd, token);
+                self.registration = Some((registration, waker, interest));
+                Poll::Pending
+            }
+            // The reactor is unable to poll this file descriptor.
+            Err(why) => {
+                self.error = Some(why);
+                ready.fetch_or(Interest::INVALID.bits(), Ordering::SeqCst);
+                Poll::Ready(Interest::INVALID)
+            }
+        }
+    }
+}
+
+/// The outcome of waiting on a file descriptor with a timeout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Readiness {
+    /// The file descriptor became ready with the given events.
+    Ready(ReadyEvents),
+
+    /// The timeout elapsed before the file descriptor became ready.
+    TimedOut,
+}
+
+/// A future which resolves when the file descriptor is ready, or once its timeout elapses.
+///
+/// Created with [`FdFuture::with_timeout`].
+pub struct FdTimeoutFuture<'a> {
+    future: FdFuture<'a>,
+}
+
+impl<'a> Future for FdTimeoutFuture<'a> {
+    type Output = Readiness;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        Pin::new(&mut self.future).poll(cx).map(|events| {
+            if events.contains(Interest::TIMEOUT) {
+                Readiness::TimedOut
+            } else {
+                Readiness::Ready(events)
+            }
+        })
+    }
+}
+
+/// A future which resolves to the events observed on the file descriptor, or to the error
+/// which its error conditions represent.
+///
+/// Created with [`FdFuture::try_ready`]. A file descriptor which the reactor refused to
+/// register resolves to the error that the registration failed with, such as when the
+/// reactor reached its [maximum](crate::ReactorBuilder::max_fds) number of file
+/// descriptors. A closed file descriptor resolves to `EBADF`, and an error condition on a
+/// socket resolves to its pending `SO_ERROR`. Should the reactor
+/// itself have failed, its [last error](Handle::last_error) is returned instead, and a
+/// reactor which has shut down resolves to [`io::ErrorKind::BrokenPipe`].
+pub struct TryFdFuture<'a> {
+    future: FdFuture<'a>,
+}
+
+impl<'a> Future for TryFdFuture<'a> {
+    type Output = io::Result<ReadyEvents>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let events = match Pin::new(&mut self.future).poll(cx) {
+            Poll::Ready(events) => events,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let waiting = &self.future.waiting;
+        if let Some(why) = &waiting.error {
+            return Poll::Ready(Err(clone_error(why)));
+        }
+
+        Poll::Ready(events_result(waiting.handle, waiting.fd, events))
+    }
+}
+
+/// Converts the error conditions among the events observed on a file descriptor into the
+/// error which they represent.
+pub(crate) fn events_result(
+    handle: &Handle,
+    fd: RawFd,
+    events: ReadyEvents,
+) -> io::Result<ReadyEvents> {
+    if events.contains(Interest::INVALID) {
+        return Err(io::Error::from_raw_os_error(libc::EBADF));
+    }
+
+    if events.contains(Interest::TIMEOUT) {
+        return Err(io::ErrorKind::TimedOut.into());
+    }
+
+    if events.contains(Interest::ERROR) {
+        let error = crate::sys::socket_error(fd)
+            .or_else(|| handle.last_error())
+            .unwrap_or_else(|| {
+                if handle.is_shutdown() {
+                    io::Error::new(io::ErrorKind::BrokenPipe, "reactor has shut down")
+                } else {
+                    io::Error::other("error condition on the file descriptor")
+                }
+            });
+
+        return Err(error);
+    }
+
+    Ok(events)
+}
+
+/// A future which waits on several file descriptors, and resolves with the first of them
+/// to become ready.
+///
+/// Every file descriptor is unregistered once any of them becomes ready, or when the
+/// future is dropped. A future created without any file descriptors never resolves.
+pub struct AnyReady<'a> {
+    futures: Vec<FdFuture<'a>>,
+}
+
+impl AnyReady<'static> {
+    /// Creates a future which waits for the given interests on each file descriptor.
+    pub fn new<I: IntoIterator<Item = (RawFd, Interest)>>(fds: I) -> Self {
+        Self::new_with(&REACTOR, fds)
+    }
+}
+
+impl<'a> AnyReady<'a> {
+    /// Creates a future which waits on the reactor of the given handle.
+    pub fn new_with<I: IntoIterator<Item = (RawFd, Interest)>>(handle: &'a Handle, fds: I) -> Self {
+        let futures = fds
+            .into_iter()
+            .map(|(fd, interest)| FdFuture::new_with(handle, fd, interest))
+            .collect();
+
+        Self { futures }
+    }
+}
+
+impl<'a> Future for AnyReady<'a> {
+    type Output = (RawFd, ReadyEvents);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let ready =
+            self.futures
+                .iter_mut()
+                .find_map(|future| match Pin::new(&mut *future).poll(cx) {
+                    Poll::Ready(events) => Some((future.waiting.fd, events)),
+                    Poll::Pending => None,
+                });
+
+        match ready {
+            Some(ready) => {
+                // Dropping the remaining futures unregisters their file descriptors.
+                self.futures.clear();
+                Poll::Ready(ready)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// A future which owns the file descriptor that it waits on.
+///
+/// The file descriptor is closed only after it has been unregistered from the reactor,
+/// and is handed back to the caller alongside the observed events on completion.
+pub struct OwnedFdFuture {
+    // Declared before the file descriptor so that it is unregistered before being closed.
+    future: FdFuture<'static>,
+    fd: Option<OwnedFd>,
+}
+
+impl OwnedFdFuture {
+    /// Creates a future which waits for the given interest on the owned file descriptor.
+    pub fn new(fd: OwnedFd, interest: Interest) -> Self {
+        Self {
+            future: FdFuture::new(fd.as_raw_fd(), interest),
+            fd: Some(fd),
+        }
+    }
+}
+
+impl Future for OwnedFdFuture {
+    type Output = (OwnedFd, ReadyEvents);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let events = match Pin::new(&mut self.future).poll(cx) {
+            Poll::Ready(events) => events,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let fd = self
+            .fd
+            .take()
+            .expect("OwnedFdFuture polled after completion");
+        Poll::Ready((fd, events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, Reactor};
+    use std::pin::pin;
+
+    /// Both futures resolve with the events which the reactor observed, and leave no
+    /// registration behind once they have.
+    #[test]
+    fn futures_resolve_and_unregister() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (reader, writer) = pipe();
+        let fd = reader.as_raw_fd();
+
+        let mut future = FdFuture::new_with(&handle, fd, Interest::READ);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert_eq!(handle.waiter_count(fd), 1);
+
+        write_byte(&writer);
+        assert!(block_on(future).is_readable());
+        assert!(!handle.is_registered(fd));
+
+        let future = pin!(InlineFdFuture::new_with(&handle, fd, Interest::READ));
+        assert!(block_on(future).is_readable());
+        assert!(!handle.is_registered(fd));
+    }
+
+    /// A future which is dropped while it waits unregisters its file descriptor, rather
+    /// than leaving the reactor polling it on behalf of nothing.
+    #[test]
+    fn pending_futures_unregister_once_dropped() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (reader, _writer) = pipe();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = FdFuture::new_with(&handle, reader.as_raw_fd(), Interest::READ);
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert_eq!(handle.registered(), [(reader.as_raw_fd(), Interest::READ)]);
+
+        drop(future);
+        assert!(handle.registered().is_empty());
+    }
+
+    /// A future resolves with only the events which occurred, among those it waited for.
+    #[test]
+    fn futures_resolve_with_the_events_which_occurred() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (_reader, writer) = pipe();
+
+        let future = FdFuture::new_with(&handle, writer.as_raw_fd(), Interest::BOTH);
+        assert_eq!(block_on(future), Interest::WRITE);
+    }
+
+    /// A future with a timeout resolves with the events of a file descriptor which becomes
+    /// ready in time, and otherwise resolves once the timeout elapses.
+    #[test]
+    fn timeouts_resolve_either_way() {
+        let (reader, writer) = pipe();
+        let fd = reader.as_raw_fd();
+        let timeout = Duration::from_millis(20);
+
+        let started = Instant::now();
+        let future = FdFuture::with_timeout(fd, Interest::READ, timeout);
+        assert_eq!(block_on(future), Readiness::TimedOut);
+        assert!(started.elapsed() >= timeout);
+        assert!(!REACTOR.is_registered(fd));
+
+        write_byte(&writer);
+        let future = FdFuture::with_timeout(fd, Interest::READ, TIMEOUT);
+        assert_eq!(block_on(future), Readiness::Ready(Interest::READ));
+        assert!(!REACTOR.is_registered(fd));
+    }
+
+    /// A future which resolved keeps its events until it is reset, after which it waits on
+    /// the reactor again.
+    #[test]
+    fn reset_futures_wait_again() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (reader, writer) = pipe();
+        let fd = reader.as_raw_fd();
+
+        write_byte(&writer);
+        let mut future = FdFuture::new_with(&handle, fd, Interest::READ);
+        assert!(block_on(&mut future).is_readable());
+        assert!(block_on(&mut future).is_readable());
+
+        let mut drained = [0u8; 1];
+        assert_eq!(unsafe { libc::read(fd, drained.as_mut_ptr().cast(), 1) }, 1);
+        future.reset();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert!(handle.is_registered(fd));
+
+        write_byte(&writer);
+        assert!(block_on(future).is_readable());
+    }
+
+    /// A future waiting for priority events is woken by out-of-band data on a TCP socket.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn futures_resolve_with_urgent_data() {
+        use std::net::{TcpListener, TcpStream};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (receiver, _) = listener.accept().unwrap();
+
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let fd = receiver.as_raw_fd();
+        let mut future = FdFuture::new_with(&handle, fd, Interest::PRIORITY);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+
+        let sent =
+            unsafe { libc::send(sender.as_raw_fd(), b"!".as_ptr().cast(), 1, libc::MSG_OOB) };
+        assert_eq!(sent, 1, "send: {}", io::Error::last_os_error());
+        assert_eq!(block_on(future), Interest::PRIORITY);
+    }
+
+    /// A fallible future resolves to the events of a file descriptor which became ready,
+    /// and to the pending error of a socket whose connection was refused.
+    #[test]
+    fn fallible_futures_resolve_to_errors() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (reader, writer) = pipe();
+        write_byte(&writer);
+
+        let future = FdFuture::new_with(&handle, reader.as_raw_fd(), Interest::READ);
+        assert_eq!(block_on(future.try_ready()).unwrap(), Interest::READ);
+
+        // Nothing listens on the port once its listener was dropped.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .port();
+        let socket = connect(port);
+
+        let future = FdFuture::new_with(&handle, socket.as_raw_fd(), Interest::WRITE);
+        let why = block_on(future.try_ready()).unwrap_err();
+        assert_eq!(why.raw_os_error(), Some(libc::ECONNREFUSED));
+    }
+
+    /// Waiting on several file descriptors resolves with the one which became ready, and
+    /// unregisters the others.
+    #[test]
+    fn any_ready_resolves_with_the_ready_file_descriptor() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let pipes = [pipe(), pipe(), pipe()];
+        let fds = pipes.each_ref().map(|(reader, _)| reader.as_raw_fd());
+
+        let mut future = AnyReady::new_with(&handle, fds.map(|fd| (fd, Interest::READ)));
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert_eq!(handle.registered().len(), fds.len());
+
+        write_byte(&pipes[1].1);
+        assert_eq!(block_on(future), (fds[1], Interest::READ));
+        assert!(handle.registered().is_empty());
+    }
+
+    /// Futures which were pending when the reactor shut down, and those created afterwards,
+    /// resolve to the reactor's terminal error rather than waiting forever.
+    #[test]
+    fn futures_resolve_to_errors_once_shut_down() {
+        let (reactor, handle) = Reactor::new().unwrap();
+        let (reader, _writer) = pipe();
+        let fd = reader.as_raw_fd();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut pending = FdFuture::new_with(&handle, fd, Interest::READ).try_ready();
+        assert!(Pin::new(&mut pending).poll(&mut cx).is_pending());
+
+        reactor.shutdown();
+        let future = FdFuture::new_with(&handle, fd, Interest::READ).try_ready();
+        for future in [pending, future] {
+            let why = block_on(future).unwrap_err();
+            assert_eq!(why.kind(), io::ErrorKind::BrokenPipe);
+        }
+    }
+
+    /// A future waiting for the peer to shut down its writing half resolves once it has,
+    /// while the connection itself remains open.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn futures_resolve_once_the_peer_shuts_down_writing() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (socket, peer) = socketpair();
+
+        let mut future = FdFuture::new_with(&handle, socket.as_raw_fd(), Interest::READ_HANGUP);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+
+        assert_eq!(
+            unsafe { libc::shutdown(peer.as_raw_fd(), libc::SHUT_WR) },
+            0
+        );
+        assert_eq!(block_on(future), Interest::READ_HANGUP);
+    }
+
+    /// Polling again with the same waker, once the interest changed, modifies the
+    /// registration in place rather than registering the file descriptor again.
+    #[test]
+    fn changed_interests_are_modified_in_place() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (socket, _peer) = socketpair();
+        let fd = socket.as_raw_fd();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = FdFuture::new_with(&handle, fd, Interest::READ);
+        let token = |future: &FdFuture| future.waiting.registration.as_ref().unwrap().0.token();
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        let registered = token(&future);
+
+        future.waiting.interest = Interest::PRIORITY;
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert_eq!(token(&future), registered);
+        assert_eq!(handle.waiter_count(fd), 1);
+        assert_eq!(handle.effective_interest(fd), Some(Interest::PRIORITY));
+
+        future.set_interest(Interest::WRITE);
+        assert_eq!(token(&future), registered);
+        assert_eq!(block_on(future), Interest::WRITE);
+    }
+
+    /// The error which the reactor refused a registration with is returned, rather than
+    /// being mistaken for a closed file descriptor.
+    #[test]
+    fn refused_registrations_return_their_error() {
+        let (_reactor, handle) = Reactor::builder().max_fds(1).build().unwrap();
+        let (first, _) = pipe();
+        let (second, _) = pipe();
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = FdFuture::new_with(&handle, first.as_raw_fd(), Interest::READ);
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+
+        let refused = FdFuture::new_with(&handle, second.as_raw_fd(), Interest::READ);
+        let why = block_on(refused.try_ready()).unwrap_err();
+        assert_eq!(why.raw_os_error(), None);
+        assert!(why.to_string().contains("maximum number"), "{}", why);
+    }
+
+    /// With the readiness cache, a future on a file descriptor which the reactor already
+    /// found ready resolves on its first poll without registering, until the readiness is
+    /// cleared, or the file descriptor refers to another file.
+    #[test]
+    fn cached_readiness_resolves_without_registering() {
+        let (_reactor, handle) = Reactor::builder().readiness_cache().build().unwrap();
+        let (reader, writer) = pipe();
+        let fd = reader.as_raw_fd();
+
+        write_byte(&writer);
+        assert!(block_on(FdFuture::new_with(&handle, fd, Interest::READ)).is_readable());
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = FdFuture::new_with(&handle, fd, Interest::READ);
+        let polled = Pin::new(&mut future).poll(&mut cx);
+        assert_eq!(polled, Poll::Ready(Interest::READ));
+        assert!(!handle.is_registered(fd));
+
+        // Cleared by resetting the future, as though the pipe had been drained.
+        future.reset();
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert!(handle.is_registered(fd));
+        drop(future);
+
+        // Found ready again, and then replaced by another pipe under the same number.
+        assert!(block_on(FdFuture::new_with(&handle, fd, Interest::READ)).is_readable());
+        assert_eq!(handle.cached_readiness(fd, Interest::READ), Interest::READ);
+
+        let (other, _other_writer) = pipe();
+        assert_ne!(unsafe { libc::dup2(other.as_raw_fd(), fd) }, -1);
+        assert!(handle.cached_readiness(fd, Interest::READ).is_empty());
+    }
+}
@@ -5,153 +5,256 @@
 //! - The reactor's background thread is spawned on the first time that the reactor handle is fetched.
 //! - Each file descriptor registers an interest to listen for.
 //! - On registering a new file descriptor, a pipe is used to interrupt the poll operation.
+//! - With the `epoll` feature on Linux, interests are updated incrementally on an epoll instance, rather than rebuilding the set of polled file descriptors on every wakeup.
+//! - With the `kqueue` feature on macOS and the BSDs, filters are likewise updated incrementally on a kqueue.
+//! - With the `mio` feature, sources from `mio` may be registered with `Handle::register_mio`.
+//! - With the `tokio` feature, `TokioFd` implements tokio's I/O traits on top of this reactor.
+//! - With the `tracing` feature, each iteration of the reactor's loop is traced, and iterations which are slow to wake their registrations are warned of.
+//! - An [`FdFuture`] unregisters its file descriptor on completion, or when it is dropped.
 
-use once_cell::sync::Lazy;
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, Read, Write},
-    os::unix::io::{AsRawFd, FromRawFd, RawFd},
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
-    },
-    task::Waker,
+mod async_fd;
+mod completion;
+mod ext;
+mod future;
+mod metrics;
+#[cfg(feature = "mio")]
+mod mio_compat;
+mod reactor;
+mod select;
+#[cfg(target_os = "linux")]
+mod signal_fd;
+mod stream;
+mod sys;
+#[cfg(test)]
+mod test_util;
+#[cfg(target_os = "linux")]
+mod timer_fd;
+#[cfg(feature = "tokio")]
+mod tokio_compat;
+
+pub use self::async_fd::{AsyncFd, ReadyGuard};
+pub use self::completion::Completion;
+pub use self::ext::{connected, readable, readable_timeout, ready, writable, writable_timeout};
+pub use self::future::{
+    AnyReady, FdFuture, FdTimeoutFuture, InlineFdFuture, OwnedFdFuture, Readiness, TryFdFuture,
 };
+pub use self::metrics::{ReactorMetrics, StepReport};
+pub use self::reactor::{Handle, Mode, Reactor, ReactorBuilder, Registration, Token};
+pub use self::select::{wait_sets, ReadySets};
+#[cfg(target_os = "linux")]
+pub use self::signal_fd::SignalFd;
+pub use self::stream::ReadinessStream;
+#[cfg(target_os = "linux")]
+pub use self::timer_fd::TimerFd;
+#[cfg(feature = "tokio")]
+pub use self::tokio_compat::TokioFd;
 
-type ReactorFds = Arc<Mutex<HashMap<RawFd, (Interest, Arc<AtomicBool>, Waker)>>>;
+use once_cell::sync::Lazy;
 
 bitflags::bitflags! {
     /// Events that should be listened for on a given file descriptor.
-    pub struct Interest: i16 {
+    pub struct Interest: libc::c_short {
         /// Listen for read events
         const READ = libc::POLLIN;
 
         /// Listen for write events
         const WRITE = libc::POLLOUT;
 
-        /// Listen for both read and write events
-        const BOTH = libc::POLLIN | libc::POLLOUT;
+        /// Listen for priority events, such as out-of-band data on a TCP socket
+        ///
+        /// These are not reported by the `kqueue` backend.
+        const PRIORITY = libc::POLLPRI;
+
+        /// The peer of a socket shut down its writing half of the connection
+        ///
+        /// Unlike [`Interest::HANGUP`], this is only reported when it is requested, and
+        /// only on Linux and Android.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        const READ_HANGUP = libc::POLLRDHUP;
+
+        /// The peer closed its end of the file descriptor
+        const HANGUP = libc::POLLHUP;
+
+        /// An error condition occurred on the file descriptor
+        const ERROR = libc::POLLERR;
+
+        /// The file descriptor is not open
+        const INVALID = libc::POLLNVAL;
+
+        /// The deadline of the registration elapsed before the file descriptor was ready
+        const TIMEOUT = libc::c_short::MIN;
     }
 }
 
-/// A handle to the reactor, for registering and unregistering file descriptors.
-pub struct Handle {
-    /// A set of file descriptors which are currently registered on the reactor.
-    fds: ReactorFds,
+// Every poll flag must be a distinct bit, which is clear of the sign bit that the reactor
+// uses for timeouts, so that no flag is lost or misreported when converting `revents`.
+const _: () = {
+    let flags = [
+        libc::POLLIN,
+        libc::POLLOUT,
+        libc::POLLPRI,
+        libc::POLLHUP,
+        libc::POLLERR,
+        libc::POLLNVAL,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        libc::POLLRDHUP,
+    ];
 
-    /// The write end of the pipe, for interrupting the poll operation.
-    interrupt: File,
-}
+    let mut seen: libc::c_short = 0;
+    let mut index = 0;
+    while index < flags.len() {
+        let flag = flags[index];
+        assert!(
+            flag > 0 && flag & (flag - 1) == 0,
+            "poll flags must be single bits"
+        );
+        assert!(seen & flag == 0, "poll flags must be distinct");
+        seen |= flag;
+        index += 1;
+    }
+
+    assert!(seen & libc::c_short::MIN == 0);
+};
+
+/// The events which were observed on a file descriptor when the reactor woke its future.
+pub type ReadyEvents = Interest;
+
+impl Interest {
+    /// Listen for both read and write events
+    // Declared outside of the flags, so that it is not repeated when formatting them.
+    pub const BOTH: Self = Self::from_bits_truncate(Self::READ.bits | Self::WRITE.bits);
+
+    /// Conditions which `poll` always reports, even when they were not requested.
+    const UNMASKABLE: Self =
+        Self::from_bits_truncate(Self::HANGUP.bits | Self::ERROR.bits | Self::INVALID.bits);
+
+    /// Conditions which persist once reported, and remove registrations which observe them
+    /// unless they are [persistent](crate::Mode::Persistent).
+    const TERMINAL: Self = Self::from_bits_truncate(Self::HANGUP.bits | Self::ERROR.bits);
+
+    /// Converts the `revents` which `poll` returned for a file descriptor, discarding any
+    /// bits which are not known to this crate.
+    ///
+    /// The sign bit of `revents` is never mistaken for [`Interest::TIMEOUT`], which only the
+    /// reactor reports.
+    pub fn from_revents(revents: libc::c_short) -> Self {
+        Self::from_bits_truncate(revents) - Self::TIMEOUT
+    }
 
-impl Handle {
-    /// Register a new file descriptor onto the reactor.
-    pub fn register(
-        &self,
-        fd: RawFd,
-        interest: Interest,
-        completed: Arc<AtomicBool>,
-        waker: Waker,
-    ) {
-        let mut lock = self.fds.lock().unwrap();
-        lock.insert(fd, (interest, completed, waker));
-        let _ = self.interrupt.try_clone().unwrap().write_all(b"0");
+    /// Converts an `events` mask for `poll`, such as one ported from C, failing with `None`
+    /// if it has any bits which are not known to this crate, rather than discarding them.
+    ///
+    /// [`Interest::TIMEOUT`] is only reported by the reactor, and so is rejected as well.
+    ///
+    /// ```
+    /// use fd_reactor::Interest;
+    ///
+    /// assert_eq!(Interest::from_events(libc::POLLIN | libc::POLLOUT), Some(Interest::BOTH));
+    /// assert_eq!(Interest::from_events(0), Some(Interest::empty()));
+    /// assert_eq!(Interest::from_events(0x4000), None);
+    /// ```
+    pub fn from_events(events: libc::c_short) -> Option<Self> {
+        Self::from_bits(events).filter(|interest| !interest.contains(Self::TIMEOUT))
     }
 
-    /// Unregister the given file descriptor from the reactor.
-    pub fn unregister(&self, fd: RawFd) {
-        let mut lock = self.fds.lock().unwrap();
-        lock.remove(&fd);
-        let _ = self.interrupt.try_clone().unwrap().write_all(b"0");
+    /// Converts the interest into the `events` which `poll` listens for, without the flags
+    /// which only the reactor reports.
+    pub fn to_events(self) -> libc::c_short {
+        (self & !Self::TIMEOUT).bits()
+    }
+
+    /// Whether the file descriptor can be read from without blocking.
+    pub fn is_readable(self) -> bool {
+        self.contains(Self::READ)
+    }
+
+    /// Whether the file descriptor can be written to without blocking.
+    pub fn is_writable(self) -> bool {
+        self.contains(Self::WRITE)
     }
 }
 
 /// Fetches the handle to the reactor which is running in a background thread.
+///
+/// Panics on first use if the reactor could not be started.
+///
+/// A child process which was forked after the reactor was started reinitializes it once
+/// the child first registers onto it, discarding the registrations of its parent, whose
+/// futures are never woken within the child.
 pub static REACTOR: Lazy<Handle> = Lazy::new(|| {
-    // Create a pipe to use as an interruption mechanism.
-    let (mut reader, writer) = create_pipe();
-
-    let fds: ReactorFds = Arc::default();
-    let fds_ = fds.clone();
-
-    std::thread::spawn(move || {
-        let fds = fds_;
-        let mut pollers = Vec::new();
-        let mut buffer = [0u8; 1];
-
-        pollers.push(libc::pollfd {
-            fd: reader.as_raw_fd(),
-            events: libc::POLLIN,
-            revents: 0,
-        });
-
-        loop {
-            let returned = unsafe {
-                let pollers: &mut [libc::pollfd] = &mut pollers;
-                libc::poll(
-                    pollers as *mut _ as *mut libc::pollfd,
-                    pollers.len() as u64,
-                    -1,
-                )
-            };
-
-            if returned == -1 {
-                panic!(
-                    "fatal error in process reactor: {}",
-                    io::Error::last_os_error()
+    let (reactor, handle) = Reactor::builder()
+        .reinit_after_fork()
+        .build()
+        .expect("failed to start the global fd-reactor");
+
+    // The global reactor runs for the lifetime of the program.
+    std::mem::forget(reactor);
+
+    handle
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every combination of the poll constants which `revents` may hold survives the
+    /// conversion, whereas the sign bit, and the constants which this crate does not know
+    /// of, are discarded without affecting the others.
+    #[test]
+    fn every_combination_of_revents_converts() {
+        let flags = [
+            (libc::POLLIN, Interest::READ),
+            (libc::POLLOUT, Interest::WRITE),
+            (libc::POLLPRI, Interest::PRIORITY),
+            (libc::POLLHUP, Interest::HANGUP),
+            (libc::POLLERR, Interest::ERROR),
+            (libc::POLLNVAL, Interest::INVALID),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            (libc::POLLRDHUP, Interest::READ_HANGUP),
+        ];
+        let unknown = libc::POLLRDNORM | libc::c_short::MIN;
+
+        for combination in 0..1u32 << flags.len() {
+            let (revents, expected) = flags
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| combination & 1 << index != 0)
+                .fold(
+                    (0, Interest::empty()),
+                    |(revents, expected), (_, &(flag, interest))| {
+                        (revents | flag, expected | interest)
+                    },
                 );
-            } else if returned < 1 {
-                continue;
-            }
-
-            let lock = fds.lock().unwrap();
-            if pollers[0].revents == libc::POLLIN {
-                let _ = reader.read(&mut buffer);
-            } else {
-                pollers[1..]
-                    .iter()
-                    .filter(|event| event.revents != 0)
-                    .for_each(|event| {
-                        if let Some(value) = lock.get(&event.fd) {
-                            if value
-                                .0
-                                .contains(Interest::from_bits_truncate(event.revents))
-                            {
-                                value.1.store(true, Ordering::SeqCst);
-                                value.2.wake_by_ref();
-                            }
-                        }
-                    })
-            }
-
-            pollers.clear();
-
-            pollers.push(libc::pollfd {
-                fd: reader.as_raw_fd(),
-                events: libc::POLLIN,
-                revents: 0,
-            });
-
-            for (&fd, &(interest, _, _)) in lock.iter() {
-                pollers.push(libc::pollfd {
-                    fd,
-                    events: interest.bits(),
-                    revents: 0,
-                });
-            }
-        }
-    });
 
-    Handle {
-        fds,
-        interrupt: writer,
+            assert_eq!(Interest::from_revents(revents), expected);
+            assert_eq!(Interest::from_revents(revents | unknown), expected);
+        }
     }
-});
 
-fn create_pipe() -> (File, File) {
-    let mut fds = [0; 2];
-    unsafe { libc::pipe(&mut fds as *mut _ as *mut libc::c_int) };
-    let reader = unsafe { File::from_raw_fd(fds[0]) };
-    let writer = unsafe { File::from_raw_fd(fds[1]) };
-    (reader, writer)
+    /// Every combination of poll flags converts to `events` and back unchanged, whereas the
+    /// timeout is only ever reported by the reactor, and unknown bits are discarded or
+    /// rejected.
+    #[test]
+    fn interests_round_trip_through_poll_flags() {
+        let known = (Interest::all() - Interest::TIMEOUT).bits();
+        let interests = (0..=known)
+            .filter(|bits| bits & !known == 0)
+            .map(Interest::from_bits_truncate);
+
+        for interest in interests {
+            let events = interest.to_events();
+            assert_eq!(events, interest.bits());
+            assert_eq!(Interest::from_events(events), Some(interest));
+            assert_eq!(Interest::from_revents(events), interest);
+        }
+
+        let timed_out = Interest::READ | Interest::TIMEOUT;
+        assert_eq!(timed_out.to_events(), libc::POLLIN);
+        assert_eq!(Interest::from_revents(timed_out.bits()), Interest::READ);
+        assert_eq!(Interest::from_events(timed_out.bits()), None);
+
+        let unknown = 0x4000 | libc::POLLOUT;
+        assert_eq!(Interest::from_revents(unknown), Interest::WRITE);
+        assert_eq!(Interest::from_events(unknown), None);
+    }
 }
@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A snapshot of the counters which a reactor keeps about its background thread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReactorMetrics {
+    /// How many times the background thread has returned from polling.
+    pub poll_iterations: u64,
+
+    /// How many wakers the reactor has woken.
+    pub wakeups: u64,
+
+    /// How many file descriptors are currently registered.
+    pub registered_fds: usize,
+
+    /// How many events were reported for file descriptors which no registration was
+    /// interested in.
+    ///
+    /// Registrations are only ever woken by events of their interest, or by error and
+    /// hangup conditions, so these events wake nothing. They are usually reported when
+    /// registrations are unregistered or modified while the reactor waits, before it could
+    /// apply the change. A task which is woken while its file descriptor is not ready was
+    /// therefore woken by something other than the reactor, or its readiness was consumed
+    /// before it was polled.
+    pub spurious_wakeups: u64,
+}
+
+/// What a single iteration of the reactor's loop observed, as returned by
+/// [`Reactor::step_once`](crate::Reactor::step_once).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepReport {
+    /// How many file descriptors were reported ready, besides the reactor's own.
+    pub ready: usize,
+
+    /// How many of the ready file descriptors had events which a registration was
+    /// interested in, or were found to be closed.
+    pub matched: usize,
+
+    /// How many distinct wakers were woken.
+    pub woken: usize,
+}
+
+/// Counters which the background thread increments as it polls.
+#[derive(Default)]
+pub(crate) struct Counters {
+    pub poll_iterations: AtomicU64,
+    pub wakeups: AtomicU64,
+    pub spurious_wakeups: AtomicU64,
+}
+
+impl Counters {
+    /// Counters are only ever read as a diagnostic, so no ordering is required of them.
+    pub fn increment(counter: &AtomicU64, by: u64) {
+        counter.fetch_add(by, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, registered_fds: usize) -> ReactorMetrics {
+        ReactorMetrics {
+            poll_iterations: self.poll_iterations.load(Ordering::Relaxed),
+            wakeups: self.wakeups.load(Ordering::Relaxed),
+            registered_fds,
+            spurious_wakeups: self.spurious_wakeups.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Adapters for registering `mio` sources onto the reactor, rather than onto a `mio::Poll`.
+
+use crate::{Completion, Handle, Interest, Token};
+use std::{io, os::unix::io::AsRawFd, task::Waker};
+
+impl From<mio::Interest> for Interest {
+    fn from(interest: mio::Interest) -> Self {
+        let mut converted = Interest::empty();
+
+        if interest.is_readable() {
+            converted |= Interest::READ;
+        }
+
+        if interest.is_writable() {
+            converted |= Interest::WRITE;
+        }
+
+        if interest.is_priority() {
+            converted |= Interest::PRIORITY;
+        }
+
+        converted
+    }
+}
+
+impl Handle {
+    /// Register a `mio` source onto the reactor, with `mio`'s interests.
+    ///
+    /// Every source which `mio` provides on Unix exposes its file descriptor, so it may
+    /// be polled by this reactor without `mio::Poll`. The source must not be registered
+    /// onto a `mio::Poll` at the same time.
+    pub fn register_mio<S, C>(
+        &self,
+        source: &S,
+        interest: mio::Interest,
+        ready: C,
+        waker: Waker,
+    ) -> io::Result<Token>
+    where
+        S: mio::event::Source + AsRawFd + ?Sized,
+        C: Completion + 'static,
+    {
+        self.register_source(source, interest.into(), ready, waker)
+    }
+}
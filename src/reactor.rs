@@ -0,0 +1,2354 @@
+use crate::{
+    completion::Completion,
+    metrics::{Counters, ReactorMetrics, StepReport},
+    sys::{Interrupt, Poller, SignalMask},
+    Interest,
+};
+use once_cell::sync::OnceCell;
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt, io, iter, mem,
+    os::unix::io::{AsRawFd, BorrowedFd, OwnedFd, RawFd},
+    sync::{
+        atomic::{AtomicBool, AtomicI16, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    task::{Wake, Waker},
+    thread::{self, JoinHandle, Thread},
+    time::{Duration, Instant},
+};
+
+/// Identifies a single registration of a file descriptor on the reactor.
+///
+/// Tokens are unique across every reactor of the process, so that a registration keeps its
+/// token once it is moved onto another reactor by [`Reactor::drain_into`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Token(u64);
+
+/// The value of the next token to be handed out on registration, by any reactor.
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+/// How a registration is woken while its file descriptor remains ready.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Woken by every poll which finds the file descriptor ready, until it is unregistered.
+    ///
+    /// Error and hangup conditions are reported by every poll once they occur, and would
+    /// spin the reactor for as long as the registration remains, so the registration is
+    /// removed once it has been woken by one of them.
+    #[default]
+    Level,
+
+    /// Woken only when events arrive which are not already [pending](Completion::pending) in
+    /// `ready`, so that the registration is woken again only once its events have been
+    /// cleared.
+    ///
+    /// With the `epoll` feature, the file descriptor is registered with `EPOLLET` whenever
+    /// every registration waiting on it is edge-triggered, as are its filters with
+    /// `EV_CLEAR` with the `kqueue` feature. The `poll(2)` backend is unable to observe
+    /// edges, and falls back to comparing against the events in `ready`, which still
+    /// leaves the reactor polling a file descriptor that remains ready.
+    Edge,
+
+    /// Removed from the reactor once its first events have been delivered.
+    ///
+    /// Only this registration is removed, and other registrations which wait on the same
+    /// file descriptor remain, so that it is polled until the last of them is removed.
+    OneShot,
+
+    /// Woken as with [`Mode::Level`], but remains registered after error and hangup
+    /// conditions, for callers which handle these conditions themselves, and unregister the
+    /// file descriptor once they no longer wish to be woken by them.
+    Persistent,
+}
+
+/// A future which is waiting for events on a registered file descriptor.
+pub(crate) struct Waiter {
+    token: Token,
+    interest: Interest,
+    mode: Mode,
+    ready: Box<dyn Completion>,
+    waker: Waker,
+    deadline: Option<Instant>,
+
+    /// The duplicate file descriptor which the reactor polls on behalf of
+    /// [`Handle::register_owned`], closed once the registration is removed.
+    // Only held so that it is closed along with the registration.
+    #[allow(dead_code)]
+    owned: Option<OwnedFd>,
+
+    /// Wakers of higher priorities are woken first.
+    priority: u8,
+}
+
+/// How a registration is made, beyond its file descriptor, interest, completion and waker.
+#[derive(Default)]
+struct Options {
+    mode: Mode,
+    deadline: Option<Instant>,
+    owned: Option<OwnedFd>,
+    priority: u8,
+}
+
+/// The readiness which the reactor last observed on a file descriptor, for its
+/// [readiness cache](ReactorBuilder::readiness_cache).
+struct Readiness {
+    /// The events which were observed, and have not been cleared since.
+    events: Interest,
+
+    /// The device and inode of the file which the events were observed on.
+    file: (u64, u64),
+}
+
+/// The events which are kept by the readiness cache. Error and hangup conditions are not,
+/// as they are reported again as soon as the file descriptor is registered.
+const CACHED: Interest = Interest::from_bits_truncate(
+    Interest::READ.bits() | Interest::WRITE.bits() | Interest::PRIORITY.bits(),
+);
+
+/// The file descriptors which are registered on the reactor.
+#[derive(Default)]
+pub(crate) struct Registry {
+    /// The futures waiting on each file descriptor.
+    pub fds: HashMap<RawFd, Vec<Waiter>>,
+
+    /// File descriptors whose registrations changed since the poller was last updated.
+    pub changed: HashSet<RawFd>,
+
+    /// The deadlines of registrations, ordered from the earliest to expire.
+    deadlines: BTreeSet<(Instant, Token, RawFd)>,
+
+    /// When each registered file descriptor was last found ready.
+    last_ready: HashMap<RawFd, Instant>,
+
+    /// How many times each file descriptor was found ready in the current budget window.
+    budget: HashMap<RawFd, u32>,
+
+    /// The readiness which was last observed on each file descriptor that was registered
+    /// with the readiness cache, which is kept once its registrations are removed.
+    readiness: HashMap<RawFd, Readiness>,
+
+    /// File descriptors which exhausted their budget, and are not polled until the current
+    /// budget window ends.
+    pub deferred: HashSet<RawFd>,
+
+    /// The loop's driver, stored here while the background thread is parked, or until the
+    /// loop is run on the current thread.
+    driver: Option<Driver>,
+
+    /// The background thread, if one has ever been spawned.
+    thread: Option<JoinHandle<()>>,
+
+    /// Threads blocked in [`Handle::sync`], which are released by the next update of the
+    /// poller.
+    syncs: Vec<(Arc<AtomicBool>, Waker)>,
+
+    /// The reactor which this one was [drained](Reactor::drain_into) into, which now holds
+    /// every registration that was made on this one.
+    forward: Option<Arc<Shared>>,
+}
+
+impl Registry {
+    fn insert(&mut self, fd: RawFd, waiter: Waiter) {
+        if let Some(deadline) = waiter.deadline {
+            self.deadlines.insert((deadline, waiter.token, fd));
+        }
+
+        self.fds.entry(fd).or_default().push(waiter);
+        self.changed.insert(fd);
+    }
+
+    fn remove(&mut self, fd: RawFd, token: Token) -> Option<Waiter> {
+        let waiters = self.fds.get_mut(&fd)?;
+        let position = waiters.iter().position(|waiter| waiter.token == token)?;
+        let waiter = waiters.swap_remove(position);
+
+        if waiters.is_empty() {
+            self.fds.remove(&fd);
+            self.forget(fd);
+        }
+
+        if let Some(deadline) = waiter.deadline {
+            self.deadlines.remove(&(deadline, token, fd));
+        }
+
+        self.changed.insert(fd);
+        Some(waiter)
+    }
+
+    /// Caches the readiness which is observed on the file descriptor from now on, discarding
+    /// what was observed on another file which previously had the same number.
+    fn track_readiness(&mut self, fd: RawFd) {
+        let file = match crate::sys::file_id(fd) {
+            Some(file) => file,
+            None => return,
+        };
+
+        let readiness = self.readiness.entry(fd).or_insert(Readiness {
+            events: Interest::empty(),
+            file,
+        });
+
+        if readiness.file != file {
+            readiness.events = Interest::empty();
+            readiness.file = file;
+        }
+    }
+
+    /// Removes every registration of a file descriptor, signaling the given events and
+    /// collecting their wakers.
+    fn remove_fd(&mut self, fd: RawFd, events: Interest, wakers: &mut Vec<(u8, Waker)>) {
+        for waiter in self.fds.remove(&fd).into_iter().flatten() {
+            if let Some(deadline) = waiter.deadline {
+                self.deadlines.remove(&(deadline, waiter.token, fd));
+            }
+
+            waiter.ready.signal(events);
+            wakers.push((waiter.priority, waiter.waker));
+        }
+
+        self.forget(fd);
+        self.readiness.remove(&fd);
+        self.changed.insert(fd);
+    }
+
+    /// Removes every registration, signaling the given events and collecting their wakers.
+    fn remove_all(&mut self, events: Interest, wakers: &mut Vec<(u8, Waker)>) {
+        self.changed.extend(self.fds.keys());
+        self.deadlines.clear();
+        self.last_ready.clear();
+        self.budget.clear();
+        self.deferred.clear();
+        self.readiness.clear();
+        for waiter in self.fds.drain().flat_map(|(_, waiters)| waiters) {
+            waiter.ready.signal(events);
+            wakers.push((waiter.priority, waiter.waker));
+        }
+    }
+
+    /// Removes every registration, returning them to be moved onto another reactor.
+    fn take_all(&mut self) -> Vec<(RawFd, Waiter)> {
+        self.changed.extend(self.fds.keys());
+        self.deadlines.clear();
+        self.last_ready.clear();
+        self.budget.clear();
+        self.deferred.clear();
+        self.fds
+            .drain()
+            .flat_map(|(fd, waiters)| waiters.into_iter().map(move |waiter| (fd, waiter)))
+            .collect()
+    }
+
+    /// Signals the events to every registration of the file descriptor which they concern,
+    /// collecting the wakers of those which should be woken, and returning whether any
+    /// registration was concerned.
+    ///
+    /// The registrations which are removed once they have been delivered events are
+    /// collected into `fired` before being removed.
+    fn deliver(
+        &mut self,
+        fd: RawFd,
+        events: Interest,
+        now: Instant,
+        wakers: &mut Vec<(u8, Waker)>,
+        fired: &mut Vec<Token>,
+    ) -> bool {
+        let mut matched = false;
+        let waiters = self.fds.get(&fd).into_iter().flatten();
+        for waiter in waiters {
+            // Registrations with no interest are suspended, even from unmaskable events.
+            if waiter.interest.is_empty() {
+                continue;
+            }
+
+            if !waiter.interest.intersects(events) && !events.intersects(Interest::UNMASKABLE) {
+                continue;
+            }
+
+            matched = true;
+
+            let pending = waiter.ready.pending();
+            waiter.ready.signal(events);
+
+            match waiter.mode {
+                Mode::OneShot => fired.push(waiter.token),
+                Mode::Level | Mode::Edge if events.intersects(Interest::TERMINAL) => {
+                    fired.push(waiter.token)
+                }
+                Mode::Edge if pending.contains(events) => continue,
+                _ => (),
+            }
+
+            wakers.push((waiter.priority, waiter.waker.clone()));
+        }
+
+        if matched {
+            self.last_ready.insert(fd, now);
+
+            if let Some(readiness) = self.readiness.get_mut(&fd) {
+                readiness.events |= events & CACHED;
+            }
+        }
+
+        for token in fired.drain(..) {
+            self.remove(fd, token);
+        }
+
+        matched
+    }
+
+    /// Discards what is known of a file descriptor which no longer has registrations.
+    fn forget(&mut self, fd: RawFd) {
+        self.last_ready.remove(&fd);
+        self.budget.remove(&fd);
+        self.deferred.remove(&fd);
+    }
+
+    /// Counts a readiness of the file descriptor against its budget, deferring it once
+    /// the budget is exhausted.
+    fn spend(&mut self, fd: RawFd, budget: u32) {
+        let spent = self.budget.entry(fd).or_default();
+        *spent += 1;
+
+        if *spent >= budget {
+            self.deferred.insert(fd);
+            self.changed.insert(fd);
+        }
+    }
+
+    /// Begins a new budget window, in which deferred file descriptors are polled again.
+    fn replenish(&mut self) {
+        self.budget.clear();
+        self.changed.extend(self.deferred.drain());
+    }
+
+    /// Removes every registration whose deadline has elapsed, collecting their wakers.
+    fn expire(&mut self, now: Instant, wakers: &mut Vec<(u8, Waker)>) {
+        while let Some(&(deadline, token, fd)) = self.deadlines.iter().next() {
+            if deadline > now {
+                break;
+            }
+
+            if let Some(waiter) = self.remove(fd, token) {
+                waiter.ready.signal(Interest::TIMEOUT);
+                wakers.push((waiter.priority, waiter.waker));
+            }
+        }
+    }
+
+    /// Releases every thread blocked in [`Handle::sync`], collecting their wakers.
+    fn release_syncs(&mut self, wakers: &mut Vec<(u8, Waker)>) {
+        for (synced, waker) in self.syncs.drain(..) {
+            synced.store(true, Ordering::SeqCst);
+            wakers.push((0, waker));
+        }
+    }
+
+    /// The earliest deadline of any registration.
+    fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.iter().next().map(|&(deadline, ..)| deadline)
+    }
+}
+
+/// The combined interest of every future waiting on a file descriptor.
+pub(crate) fn merged_interest(waiters: &[Waiter]) -> Interest {
+    waiters
+        .iter()
+        .fold(Interest::empty(), |acc, waiter| acc | waiter.interest)
+}
+
+/// Whether every future waiting on a file descriptor is edge-triggered.
+#[cfg(any(
+    all(target_os = "linux", any(feature = "epoll", test)),
+    all(
+        any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ),
+        feature = "kqueue"
+    )
+))]
+pub(crate) fn edge_triggered(waiters: &[Waiter]) -> bool {
+    !waiters.is_empty() && waiters.iter().all(|waiter| waiter.mode == Mode::Edge)
+}
+
+/// State which is shared between a reactor's handles and its background thread.
+struct Shared {
+    /// A set of file descriptors which are currently registered on the reactor.
+    ///
+    /// Registrations are changed under this lock, rather than queued for the reactor's
+    /// thread to apply, so that their changes take effect before the call which made them
+    /// returns. Unregistering in particular guarantees that the registration is never
+    /// signaled again, which [`InlineFdFuture`](crate::InlineFdFuture) relies on for its
+    /// soundness, and which a queue could only provide by waiting on the thread. The lock
+    /// is held only briefly, and the thread is interrupted at most once for all the
+    /// changes which are made before it next wakes.
+    registry: Mutex<Registry>,
+
+    /// Interrupts the poll operation.
+    interrupt: Interrupt,
+
+    /// Set while the reactor has an interrupt which it has not yet woken for.
+    interrupt_pending: AtomicBool,
+
+    /// The last poll failure, if polling has ever failed.
+    error: Mutex<Option<io::Error>>,
+
+    /// Set when the background thread should stop polling.
+    shutdown: AtomicBool,
+
+    /// The number of handles to the reactor, which shuts down once the last is dropped.
+    handles: AtomicUsize,
+
+    /// How long the background thread waits without registrations before it exits.
+    idle_timeout: Option<Duration>,
+
+    /// The longest that the background thread waits before performing maintenance.
+    maintenance_interval: Option<Duration>,
+
+    /// How many times a file descriptor may be found ready within each window of time.
+    wakeup_budget: Option<(u32, Duration)>,
+
+    /// The maximum number of file descriptors which may be registered at once.
+    max_fds: Option<usize>,
+
+    /// The name given to the background thread whenever it is spawned.
+    thread_name: String,
+
+    /// How long an iteration may take to wake registrations before a warning is traced.
+    #[cfg(feature = "tracing")]
+    slow_threshold: Duration,
+
+    /// Spawns the background thread in place of `thread::Builder`, if given.
+    spawner: Option<Spawner>,
+
+    /// The process which created the reactor, which a child forked from it does not share,
+    /// or the child which last reinitialized it.
+    pid: AtomicU32,
+
+    /// Whether a child process forked from the one which created the reactor reinitializes
+    /// it, rather than failing every registration.
+    reinit_after_fork: bool,
+
+    /// The signal mask of the poller, which is created again by a reinitialization.
+    signal_mask: Option<SignalMask>,
+
+    /// Whether file descriptors in blocking mode are rejected by registrations.
+    require_nonblocking: bool,
+
+    /// Whether the readiness which is observed on file descriptors is cached.
+    readiness_cache: bool,
+
+    /// Whether the loop is run by [`Reactor::run_current_thread`], rather than on a
+    /// background thread.
+    current_thread: bool,
+
+    /// Whether the loop is [paused](Handle::pause).
+    paused: Mutex<bool>,
+
+    /// Notified once the loop is resumed.
+    resumed: Condvar,
+
+    /// Counters which are exposed through [`Handle::metrics`].
+    counters: Counters,
+}
+
+impl Shared {
+    /// Locks the registry, recovering it if another thread panicked while holding the lock.
+    ///
+    /// Registrations are only ever inserted or removed as a whole, so the registry remains
+    /// consistent even when the lock has been poisoned.
+    fn registry(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Calls the function with the reactor which holds the registrations that were made on
+    /// this one, and its locked registry, which is another reactor's once this one has been
+    /// [drained](Reactor::drain_into) into it.
+    ///
+    /// A drained reactor never holds registrations again, so its lock is released before
+    /// that of the other reactor is taken.
+    fn with_registrations<R>(
+        self: &Arc<Self>,
+        f: impl FnOnce(&Arc<Shared>, &mut Registry) -> R,
+    ) -> R {
+        let mut lock = self.registry();
+        match lock.forward.clone() {
+            Some(other) => {
+                drop(lock);
+                other.with_registrations(f)
+            }
+            None => f(self, &mut lock),
+        }
+    }
+
+    /// Wakes the reactor's thread so that it picks up changes to the registry.
+    ///
+    /// Changes made since the reactor last woke are picked up by a single wakeup, so the
+    /// interrupt is only notified if no other notification is already pending.
+    ///
+    /// Should the interrupt be unable to notify the thread, the reactor is shut down, as it
+    /// could no longer pick up changes. The thread exits once it next wakes.
+    fn interrupt(&self) {
+        if !self.interrupt_pending.swap(true, Ordering::SeqCst) && self.interrupt.notify().is_err()
+        {
+            self.shutdown.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lets the loop resume polling, should it have been paused.
+    fn resume(&self) {
+        *self.paused.lock().unwrap_or_else(PoisonError::into_inner) = false;
+        self.resumed.notify_all();
+    }
+
+    /// Blocks the loop for as long as it is paused, unless the reactor shuts down.
+    fn wait_resumed(&self) {
+        let mut paused = self.paused.lock().unwrap_or_else(PoisonError::into_inner);
+        while *paused && !self.shutdown.load(Ordering::SeqCst) {
+            paused = self
+                .resumed
+                .wait(paused)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+/// Runs the reactor's loop on a thread of the caller's choosing.
+type SpawnFn = dyn Fn(Box<dyn FnOnce() + Send>) -> io::Result<JoinHandle<()>> + Send + Sync;
+
+#[derive(Clone)]
+struct Spawner(Arc<SpawnFn>);
+
+impl fmt::Debug for Spawner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Spawner")
+    }
+}
+
+/// Configures and spawns a [`Reactor`].
+#[derive(Debug, Default)]
+pub struct ReactorBuilder {
+    idle_timeout: Option<Duration>,
+    maintenance_interval: Option<Duration>,
+    wakeup_budget: Option<(u32, Duration)>,
+    max_fds: Option<usize>,
+    thread_name: Option<String>,
+    signal_mask: Option<SignalMask>,
+    spawner: Option<Spawner>,
+    require_nonblocking: bool,
+    current_thread: bool,
+    reinit_after_fork: bool,
+    readiness_cache: bool,
+    #[cfg(feature = "tracing")]
+    slow_threshold: Option<Duration>,
+}
+
+impl ReactorBuilder {
+    /// Creates a builder with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets the background thread exit once no file descriptors have been registered for
+    /// the given duration.
+    ///
+    /// The thread is spawned again by the next registration, so idle programs do not have
+    /// to keep the thread around.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Wakes the background thread at least this often, even if no file descriptor is ready
+    /// and no deadline has elapsed.
+    ///
+    /// Each wakeup expires elapsed deadlines and updates the reactor's
+    /// [metrics](Handle::metrics) before polling again. By default, the thread waits
+    /// indefinitely for events.
+    pub fn maintenance_interval(mut self, interval: Duration) -> Self {
+        self.maintenance_interval = Some(interval);
+        self
+    }
+
+    /// Limits how many times each file descriptor may be found ready within each window of
+    /// the given duration.
+    ///
+    /// A file descriptor which remains ready, such as a fast socket which its level-triggered
+    /// registrations never drain, would otherwise have the reactor wake its registrations
+    /// on every poll, at the expense of the wakeups of every other file descriptor. Once its
+    /// budget is exhausted, a file descriptor is no longer polled until the window ends.
+    ///
+    /// A budget of zero is treated as a budget of one.
+    pub fn wakeup_budget(mut self, wakeups: u32, window: Duration) -> Self {
+        self.wakeup_budget = Some((wakeups.max(1), window));
+        self
+    }
+
+    /// Limits how many distinct file descriptors may be registered at once.
+    ///
+    /// Registrations which would exceed the limit fail, so that callers may apply their own
+    /// backpressure. File descriptors which are already registered may always be
+    /// registered again.
+    pub fn max_fds(mut self, max_fds: usize) -> Self {
+        self.max_fds = Some(max_fds);
+        self
+    }
+
+    /// Names the background thread, which is named `fd-reactor` by default.
+    pub fn thread_name(mut self, name: impl Into<String>) -> Self {
+        self.thread_name = Some(name.into());
+        self
+    }
+
+    /// Sets the signal mask of the background thread while it waits, with `ppoll(2)` or
+    /// `epoll_pwait(2)`, so that signals may be unblocked atomically with the wait.
+    ///
+    /// Building the reactor fails where this is unsupported, which is on platforms without
+    /// `ppoll`, and with the `kqueue` feature.
+    pub fn signal_mask(mut self, mask: libc::sigset_t) -> Self {
+        self.signal_mask = Some(SignalMask(mask));
+        self
+    }
+
+    /// Spawns the background thread with the given function, rather than with
+    /// `thread::Builder`, such as to configure its stack size or scheduling.
+    ///
+    /// The function is given the reactor's loop to run, and is called again whenever the
+    /// thread is respawned after exiting while [idle](ReactorBuilder::idle_timeout). The
+    /// [thread name](ReactorBuilder::thread_name) is not applied to threads which it spawns.
+    /// The returned handle is joined when the reactor shuts down.
+    pub fn spawner<F>(mut self, spawner: F) -> Self
+    where
+        F: Fn(Box<dyn FnOnce() + Send>) -> io::Result<JoinHandle<()>> + Send + Sync + 'static,
+    {
+        self.spawner = Some(Spawner(Arc::new(spawner)));
+        self
+    }
+
+    /// Rejects registrations of file descriptors which are in blocking mode, which is to say
+    /// without `O_NONBLOCK` set.
+    ///
+    /// Readiness may occasionally be reported spuriously, after which a blocking read or
+    /// write would stall the thread of its task rather than fail with `EWOULDBLOCK`. This
+    /// catches file descriptors which were never made non-blocking, at the cost of an
+    /// additional `fcntl(2)` per registration.
+    pub fn require_nonblocking(mut self) -> Self {
+        self.require_nonblocking = true;
+        self
+    }
+
+    /// Spawns no background thread, so that the reactor's loop is run by the caller with
+    /// [`Reactor::run_current_thread`], such as alongside a single-threaded executor.
+    ///
+    /// Registrations are not polled until the loop is run. The
+    /// [idle timeout](ReactorBuilder::idle_timeout), [thread name](ReactorBuilder::thread_name),
+    /// and [spawner](ReactorBuilder::spawner) do not apply to the loop.
+    pub fn current_thread(mut self) -> Self {
+        self.current_thread = true;
+        self
+    }
+
+    /// Traces a warning whenever an iteration of the reactor's loop takes longer than the
+    /// given threshold to handle its events, which is `10ms` by default.
+    ///
+    /// This is usually caused by wakers which block the reactor's thread, such as those
+    /// which poll their task inline.
+    #[cfg(feature = "tracing")]
+    pub fn slow_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+
+    /// Caches the readiness which the reactor observes on each file descriptor, so that a
+    /// future which waits on a file descriptor that was already found ready resolves without
+    /// registering onto the reactor.
+    ///
+    /// Readiness is kept until it is cleared with [`Handle::clear_readiness`], which callers
+    /// must do once an operation on the file descriptor would block, as the reactor has no
+    /// way of knowing when it was drained. [`FdFuture::reset`](crate::FdFuture::reset) clears
+    /// the readiness of its interest. Readiness which was observed on a file descriptor is
+    /// never reported for another file which later reuses its number.
+    pub fn readiness_cache(mut self) -> Self {
+        self.readiness_cache = true;
+        self
+    }
+
+    /// Reinitializes the reactor within a child process which was forked from the one that
+    /// built it, once the child first registers onto it, rather than failing registrations.
+    ///
+    /// The reactor is locked by the forking thread until the fork has completed, so that it
+    /// is never copied into the child while another thread was changing it. Only one reactor
+    /// may be built this way, which is the global [`REACTOR`](crate::REACTOR).
+    pub(crate) fn reinit_after_fork(mut self) -> Self {
+        self.reinit_after_fork = true;
+        self
+    }
+
+    /// Spawns the reactor, returning it alongside a handle for registering onto it.
+    ///
+    /// Fails if the file descriptors of the reactor could not be created, such as when the
+    /// process has run out of file descriptors, or if its thread could not be spawned.
+    pub fn build(self) -> io::Result<(Reactor, Handle)> {
+        let interrupt = Interrupt::new()?;
+        let poller = Poller::new(interrupt.as_raw_fd(), self.signal_mask)?;
+
+        let shared = Arc::new(Shared {
+            registry: Mutex::default(),
+            interrupt,
+            interrupt_pending: AtomicBool::new(false),
+            error: Mutex::default(),
+            shutdown: AtomicBool::new(false),
+            handles: AtomicUsize::new(1),
+            idle_timeout: self.idle_timeout.filter(|_| !self.current_thread),
+            maintenance_interval: self.maintenance_interval,
+            wakeup_budget: self.wakeup_budget,
+            max_fds: self.max_fds,
+            thread_name: self
+                .thread_name
+                .unwrap_or_else(|| String::from("fd-reactor")),
+            spawner: self.spawner,
+            pid: AtomicU32::new(std::process::id()),
+            reinit_after_fork: self.reinit_after_fork,
+            signal_mask: self.signal_mask,
+            require_nonblocking: self.require_nonblocking,
+            readiness_cache: self.readiness_cache,
+            current_thread: self.current_thread,
+            #[cfg(feature = "tracing")]
+            slow_threshold: self.slow_threshold.unwrap_or(Duration::from_millis(10)),
+            paused: Mutex::new(false),
+            resumed: Condvar::new(),
+            counters: Counters::default(),
+        });
+
+        if self.reinit_after_fork && FORKING_REACTOR.set(shared.clone()).is_ok() {
+            crate::sys::at_fork(prepare_fork, release_fork, release_fork)?;
+        }
+
+        let driver = Driver::new(&shared, poller);
+
+        if self.current_thread {
+            shared.registry().driver = Some(driver);
+        } else {
+            shared.registry().thread = Some(spawn(shared.clone(), driver)?);
+        }
+
+        let reactor = Reactor {
+            shared: shared.clone(),
+        };
+
+        Ok((reactor, Handle { shared }))
+    }
+}
+
+/// A reactor which polls its registered file descriptors on its own background thread.
+///
+/// Most applications should use the global [`REACTOR`](crate::REACTOR) instead, but a
+/// dedicated reactor may be created for isolation from the rest of the program. Dropping
+/// the reactor shuts it down, and waits for its thread to exit, as does dropping its
+/// handle, without waiting. A reactor may instead be built to run its loop on a thread of
+/// the caller's own, with [`ReactorBuilder::current_thread`].
+///
+/// Every reactor keeps its own registrations, and shares no state with other reactors, so
+/// a file descriptor may be registered on several reactors at once, such as to wait for
+/// reads on one and writes on another. Each reactor polls the file descriptor
+/// independently, and unregistering it from one leaves it registered on the others.
+///
+/// Reactors do not survive `fork(2)`, as their threads are not copied into the child, and
+/// the kernel objects which they poll with would be shared with the parent. A child which
+/// waits on file descriptors creates a reactor of its own, and every registration of a
+/// reactor from its parent fails. The global [`REACTOR`](crate::REACTOR) is the exception,
+/// which the child reinitializes instead.
+pub struct Reactor {
+    shared: Arc<Shared>,
+}
+
+impl Reactor {
+    /// Spawns a new reactor, returning it alongside a handle for registering onto it.
+    pub fn new() -> io::Result<(Reactor, Handle)> {
+        ReactorBuilder::new().build()
+    }
+
+    /// Creates a builder for configuring a new reactor.
+    pub fn builder() -> ReactorBuilder {
+        ReactorBuilder::new()
+    }
+
+    /// Runs the reactor's loop on the calling thread until the reactor is shut down, for a
+    /// reactor which was built to run on the [current thread](ReactorBuilder::current_thread).
+    ///
+    /// The loop is shut down by dropping the last handle to the reactor, after which every
+    /// registration resolves with [`Interest::ERROR`], as with a background thread.
+    ///
+    /// Fails if the reactor was built with a background thread, or if its loop is already
+    /// running on another thread.
+    pub fn run_current_thread(&self) -> io::Result<()> {
+        if let Some(driver) = self.take_driver()? {
+            run(&self.shared, driver);
+        }
+
+        Ok(())
+    }
+
+    /// Performs a single iteration of the reactor's loop on the calling thread, for a
+    /// reactor which was built to run on the [current thread](ReactorBuilder::current_thread),
+    /// returning a report of what it observed.
+    ///
+    /// The iteration polls the registered file descriptors, waiting no longer than the
+    /// timeout if given, signals and wakes the registrations whose events were observed,
+    /// and returns. This allows the loop to be interleaved with the ticks of an executor,
+    /// or stepped deterministically. Once the reactor has shut down, every registration
+    /// resolves with [`Interest::ERROR`], and an empty report is returned.
+    ///
+    /// Fails if the reactor was built with a background thread, or if its loop is already
+    /// running on another thread.
+    pub fn step_once(&self, timeout: Option<Duration>) -> io::Result<StepReport> {
+        // A paused loop would otherwise block the calling thread until it is resumed.
+        if self.shared.is_paused() {
+            return Ok(StepReport::default());
+        }
+
+        let mut driver = match self.take_driver()? {
+            Some(driver) => driver,
+            None => return Ok(StepReport::default()),
+        };
+
+        let mut exit = ExitGuard {
+            shared: &self.shared,
+            parked: false,
+        };
+
+        // Registrations made since the previous iteration are polled by this one, rather
+        // than only once it has been interrupted by them.
+        driver.poller.update(&mut self.shared.registry());
+
+        match driver.step(&self.shared, timeout) {
+            Step::Continue(report) => {
+                self.shared.registry().driver = Some(driver);
+                exit.parked = true;
+                Ok(report)
+            }
+            Step::Exit => Ok(StepReport::default()),
+            Step::Park(_) => unreachable!("reactors on the current thread are never idle"),
+        }
+    }
+
+    /// Takes the driver of a reactor which runs on the current thread, or `None` if the
+    /// reactor has already shut down.
+    fn take_driver(&self) -> io::Result<Option<Driver>> {
+        if !self.shared.current_thread {
+            return Err(io::Error::other(
+                "the reactor runs its loop on a background thread",
+            ));
+        }
+
+        let driver = self.shared.registry().driver.take();
+        if driver.is_none() && !self.shared.shutdown.load(Ordering::SeqCst) {
+            return Err(io::Error::other("the reactor is already running"));
+        }
+
+        Ok(driver)
+    }
+
+    /// Moves every registration onto the reactor of the given handle, and shuts this reactor
+    /// down, such as to replace it with one which uses another backend.
+    ///
+    /// Each registration keeps its token, interest, mode, deadline, completion, and waker,
+    /// and is woken by the other reactor once its events are observed there, so pending
+    /// futures complete as usual. Registrations may still be modified, unregistered, and
+    /// queried through handles to this reactor, which forward them onto the other, though
+    /// new registrations on this reactor resolve with [`Interest::ERROR`], as would every
+    /// moved registration should the other reactor have already shut down. This reactor's
+    /// thread exits once it next wakes.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the handle refers to this reactor.
+    pub fn drain_into(&self, other: &Handle) -> io::Result<()> {
+        if Arc::ptr_eq(&self.shared, &other.shared) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a reactor can not be drained into itself",
+            ));
+        }
+
+        let mut wakers = Vec::new();
+
+        // Both registries are locked while registrations are moved, so that they are always
+        // found on one reactor or the other. They are locked in the order of their addresses,
+        // so that two reactors which are drained into one another may not deadlock.
+        let (mut lock, mut target) = if Arc::as_ptr(&self.shared) < Arc::as_ptr(&other.shared) {
+            let lock = self.shared.registry();
+            (lock, other.shared.registry())
+        } else {
+            let target = other.shared.registry();
+            (self.shared.registry(), target)
+        };
+
+        // Marked as shut down while locked, so that no registration may arrive afterwards.
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        let moved = lock.take_all();
+        let forwarded = !other.shared.shutdown.load(Ordering::SeqCst);
+
+        if forwarded {
+            for (fd, waiter) in moved {
+                target.insert(fd, waiter);
+            }
+
+            lock.forward = Some(other.shared.clone());
+        } else {
+            for (_, waiter) in moved {
+                waiter.ready.signal(Interest::ERROR);
+                wakers.push((waiter.priority, waiter.waker));
+            }
+        }
+
+        drop(lock);
+        self.shared.interrupt();
+        self.shared.resume();
+
+        if forwarded {
+            if let Err(why) = other.respawn(&mut target) {
+                return Err(other.abandon(target, why));
+            }
+
+            other.shared.interrupt();
+        }
+
+        drop(target);
+        wake_all(&self.shared, &mut wakers);
+        Ok(())
+    }
+
+    /// Stops the reactor's background thread, and waits for it to exit.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        self.shared.interrupt();
+        self.shared.resume();
+
+        let thread = self.shared.registry().thread.take();
+        if let Some(thread) = thread {
+            // The thread of a reactor from the parent of a forked process was not copied into
+            // the child, which would wait on it forever.
+            if self.shared.pid.load(Ordering::SeqCst) != std::process::id() {
+                mem::forget(thread);
+            } else {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Drop for Reactor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// A handle to the reactor, for registering and unregistering file descriptors.
+///
+/// Handles are cheap to clone, and every clone refers to the same reactor, as do the
+/// futures created from them with `new_with`.
+pub struct Handle {
+    shared: Arc<Shared>,
+}
+
+impl Clone for Handle {
+    fn clone(&self) -> Self {
+        self.shared.handles.fetch_add(1, Ordering::SeqCst);
+
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        // Once the last clone is dropped, no more registrations may be made or removed, so
+        // the thread is stopped rather than left polling forever, should its reactor have
+        // been forgotten. The thread is not joined, as handles may be dropped by the thread
+        // itself.
+        if self.shared.handles.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shared.shutdown.store(true, Ordering::SeqCst);
+            self.shared.interrupt();
+            self.shared.resume();
+        }
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("registered_fds", &self.shared.registry().fds.len())
+            .field("interrupt", &self.shared.interrupt.as_raw_fd())
+            .field("shutdown", &self.is_shutdown())
+            .finish()
+    }
+}
+
+impl Handle {
+    /// Register a new file descriptor onto the reactor.
+    ///
+    /// Once the file descriptor is ready, the observed events are signaled to the
+    /// [`Completion`] given as `ready`, such as an `Arc<AtomicI16>` which stores their bits,
+    /// and the waker is woken. Multiple registrations may wait on the same file
+    /// descriptor, and each is identified by the returned token.
+    ///
+    /// Should the reactor find that the file descriptor has been closed, every registration
+    /// of it is removed, and [`Interest::INVALID`] is signaled to `ready`.
+    ///
+    /// Regular files are always ready to be read from and written to, and would wake their
+    /// registrations on every poll, so they are never registered. The read and write
+    /// events of their interest are signaled immediately instead, once, and the returned
+    /// token refers to no registration. Terminals and other character devices are polled
+    /// as usual, though some, such as `/dev/null`, are likewise always ready, and are best
+    /// registered with [`Mode::OneShot`].
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the file descriptor is negative, or
+    /// belongs to the reactor itself, or is in blocking mode when the reactor
+    /// [requires](ReactorBuilder::require_nonblocking) otherwise, and fails if the reactor
+    /// has already reached its [maximum](ReactorBuilder::max_fds) number of registered file
+    /// descriptors, or if the reactor was created by the parent of a forked process. Should
+    /// the background thread have exited while idle, and fail to spawn again, the reactor is
+    /// shut down, and its error is returned.
+    pub fn register<C: Completion + 'static>(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        ready: C,
+        waker: Waker,
+    ) -> io::Result<Token> {
+        self.insert(fd, interest, ready, waker, Options::default())
+    }
+
+    /// Register a new file descriptor onto the reactor, which is woken according to the
+    /// given mode.
+    ///
+    /// Registrations made with [`Handle::register`] are level-triggered.
+    pub fn register_with_mode<C: Completion + 'static>(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        mode: Mode,
+        ready: C,
+        waker: Waker,
+    ) -> io::Result<Token> {
+        let options = Options {
+            mode,
+            ..Options::default()
+        };
+
+        self.insert(fd, interest, ready, waker, options)
+    }
+
+    /// Register a new file descriptor onto the reactor, whose waker is woken before those
+    /// of registrations with a lower priority.
+    ///
+    /// Registrations made with [`Handle::register`] have a priority of zero. This only
+    /// orders the wakers which the reactor wakes together, after a single poll which found
+    /// many file descriptors ready at once, such as to wake the task of a control socket
+    /// before those of bulk transfers. Registrations of the same priority are woken in no
+    /// particular order.
+    pub fn register_with_priority<C: Completion + 'static>(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        priority: u8,
+        ready: C,
+        waker: Waker,
+    ) -> io::Result<Token> {
+        let options = Options {
+            priority,
+            ..Options::default()
+        };
+
+        self.insert(fd, interest, ready, waker, options)
+    }
+
+    /// Register a new file descriptor onto the reactor, returning a guard which unregisters
+    /// it once dropped.
+    pub fn register_guarded<C: Completion + 'static>(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        ready: C,
+        waker: Waker,
+    ) -> io::Result<Registration<'_>> {
+        let token = self.register(fd, interest, ready, waker)?;
+        Ok(Registration::new(self, fd, token))
+    }
+
+    /// Register a borrowed file descriptor onto the reactor.
+    ///
+    /// The file descriptor must remain open until it is unregistered, which is guaranteed
+    /// for futures created with [`FdFuture::from_fd`](crate::FdFuture::from_fd).
+    pub fn register_borrowed<C: Completion + 'static>(
+        &self,
+        fd: BorrowedFd<'_>,
+        interest: Interest,
+        ready: C,
+        waker: Waker,
+    ) -> io::Result<Token> {
+        self.register(fd.as_raw_fd(), interest, ready, waker)
+    }
+
+    /// Register the file descriptor of any source which exposes one onto the reactor.
+    ///
+    /// The source must remain open until it is unregistered.
+    pub fn register_source<S: AsRawFd + ?Sized, C: Completion + 'static>(
+        &self,
+        source: &S,
+        interest: Interest,
+        ready: C,
+        waker: Waker,
+    ) -> io::Result<Token> {
+        self.register(source.as_raw_fd(), interest, ready, waker)
+    }
+
+    /// Register a new file descriptor onto the reactor, which expires at the given deadline.
+    ///
+    /// If the deadline elapses before the file descriptor is ready, the registration is
+    /// removed, [`Interest::TIMEOUT`] is signaled to `ready`, and the waker is woken.
+    pub fn register_with_deadline<C: Completion + 'static>(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        ready: C,
+        waker: Waker,
+        deadline: Instant,
+    ) -> io::Result<Token> {
+        let options = Options {
+            deadline: Some(deadline),
+            ..Options::default()
+        };
+
+        self.insert(fd, interest, ready, waker, options)
+    }
+
+    /// Register a new file descriptor onto the reactor, which calls the callback with the
+    /// observed events whenever the file descriptor is ready, until it is unregistered.
+    ///
+    /// This is for callers which are not running within an async runtime, and would rather
+    /// not block a thread on [`Handle::wait_ready`]. The callback runs on the reactor's
+    /// thread, after the lock of the registry has been released, so it may register and
+    /// unregister file descriptors. It must not block, however, as no other file
+    /// descriptors are polled until it returns.
+    ///
+    /// As with other level-triggered registrations, the registration is removed once the
+    /// callback has been called with an error or hangup condition.
+    pub fn register_callback(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        callback: Box<dyn FnMut(Interest) + Send>,
+    ) -> io::Result<Token> {
+        self.register_callback_with_mode(fd, interest, Mode::Level, callback)
+    }
+
+    /// Register a new file descriptor onto the reactor, which calls the callback with the
+    /// observed events according to the given mode.
+    ///
+    /// With [`Mode::OneShot`], the callback is called at most once, after which its
+    /// registration is removed without having to be unregistered.
+    pub fn register_callback_with_mode(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        mode: Mode,
+        callback: Box<dyn FnMut(Interest) + Send>,
+    ) -> io::Result<Token> {
+        let callback = Arc::new(Callback {
+            events: AtomicI16::new(0),
+            callback: Mutex::new(callback),
+        });
+
+        self.register_with_mode(fd, interest, mode, callback.clone(), Waker::from(callback))
+    }
+
+    /// Register a duplicate of the file descriptor onto the reactor, which the reactor
+    /// owns for as long as the registration remains.
+    ///
+    /// The caller may close its own file descriptor at any time, without the reactor
+    /// polling a closed file descriptor, or another which reused its number. This costs an
+    /// additional file descriptor per registration, which counts towards the process's
+    /// limit of open file descriptors.
+    ///
+    /// Returns the duplicate file descriptor alongside the token, which must both be given
+    /// to [`Handle::unregister`]. The duplicate shares its open file description with the
+    /// original, and so observes the same readiness.
+    pub fn register_owned<C: Completion + 'static>(
+        &self,
+        fd: BorrowedFd<'_>,
+        interest: Interest,
+        ready: C,
+        waker: Waker,
+    ) -> io::Result<(RawFd, Token)> {
+        self.validate(fd.as_raw_fd())?;
+
+        let owned = fd.try_clone_to_owned()?;
+        let fd = owned.as_raw_fd();
+        let options = Options {
+            owned: Some(owned),
+            ..Options::default()
+        };
+
+        let token = self.insert(fd, interest, ready, waker, options)?;
+        Ok((fd, token))
+    }
+
+    fn insert<C: Completion + 'static>(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        ready: C,
+        waker: Waker,
+        options: Options,
+    ) -> io::Result<Token> {
+        self.validate(fd)?;
+
+        let token = self.next_token();
+        let always_ready = always_ready(fd, interest);
+        let mut lock = self.shared.registry();
+
+        // Checked before anything is signaled, so that file descriptors which are always
+        // ready are refused once the reactor is at its maximum, as with `register_many`.
+        self.check_capacity(&lock, iter::once(fd))?;
+
+        if let Some(events) = always_ready {
+            drop(lock);
+            ready.signal(events);
+            waker.wake();
+            return Ok(token);
+        }
+
+        // A reactor which has shut down will never poll the file descriptor.
+        if self.shared.shutdown.load(Ordering::SeqCst) {
+            drop(lock);
+            ready.signal(Interest::ERROR);
+            waker.wake();
+            return Ok(token);
+        }
+
+        let waiter = Waiter {
+            token,
+            interest,
+            mode: options.mode,
+            ready: Box::new(ready),
+            waker,
+            deadline: options.deadline,
+            owned: options.owned,
+            priority: options.priority,
+        };
+
+        lock.insert(fd, waiter);
+
+        if self.shared.readiness_cache {
+            lock.track_readiness(fd);
+        }
+
+        if let Err(why) = self.respawn(&mut lock) {
+            return Err(self.abandon(lock, why));
+        }
+
+        self.shared.interrupt();
+        Ok(token)
+    }
+
+    /// Register many file descriptors onto the reactor at once.
+    ///
+    /// Each registration behaves as if it were registered with [`Handle::register`], but the
+    /// registry is locked only once, and the reactor is interrupted only once, for all of
+    /// them. The tokens are returned in the order that the registrations were given.
+    ///
+    /// Should any file descriptor be invalid, none of them are registered.
+    pub fn register_many<I, C>(&self, registrations: I) -> io::Result<Vec<Token>>
+    where
+        I: IntoIterator<Item = (RawFd, Interest, C, Waker)>,
+        C: Completion + 'static,
+    {
+        let registrations = registrations.into_iter().collect::<Vec<_>>();
+        for &(fd, ..) in &registrations {
+            self.validate(fd)?;
+        }
+
+        let mut tokens = Vec::with_capacity(registrations.len());
+        let mut lock = self.shared.registry();
+
+        // A reactor which has shut down will never poll the file descriptors.
+        if self.shared.shutdown.load(Ordering::SeqCst) {
+            drop(lock);
+            for (_, _, ready, waker) in registrations {
+                tokens.push(self.next_token());
+                ready.signal(Interest::ERROR);
+                waker.wake();
+            }
+
+            return Ok(tokens);
+        }
+
+        self.check_capacity(&lock, registrations.iter().map(|&(fd, ..)| fd))?;
+
+        let mut wakers = Vec::new();
+        let mut inserted = false;
+
+        for (fd, interest, ready, waker) in registrations {
+            let token = self.next_token();
+            tokens.push(token);
+
+            if let Some(events) = always_ready(fd, interest) {
+                ready.signal(events);
+                wakers.push(waker);
+                continue;
+            }
+
+            let waiter = Waiter {
+                token,
+                interest,
+                mode: Mode::Level,
+                ready: Box::new(ready),
+                waker,
+                deadline: None,
+                owned: None,
+                priority: 0,
+            };
+
+            lock.insert(fd, waiter);
+            inserted = true;
+
+            if self.shared.readiness_cache {
+                lock.track_readiness(fd);
+            }
+        }
+
+        if inserted {
+            if let Err(why) = self.respawn(&mut lock) {
+                let why = self.abandon(lock, why);
+                wakers.into_iter().for_each(Waker::wake);
+                return Err(why);
+            }
+
+            self.shared.interrupt();
+        }
+
+        drop(lock);
+        wakers.into_iter().for_each(Waker::wake);
+
+        Ok(tokens)
+    }
+
+    /// Fails if registering the file descriptors would exceed the reactor's maximum number
+    /// of registered file descriptors.
+    fn check_capacity<I: IntoIterator<Item = RawFd>>(
+        &self,
+        registry: &Registry,
+        fds: I,
+    ) -> io::Result<()> {
+        let max_fds = match self.shared.max_fds {
+            Some(max_fds) => max_fds,
+            None => return Ok(()),
+        };
+
+        let added = fds
+            .into_iter()
+            .filter(|fd| !registry.fds.contains_key(fd))
+            .collect::<HashSet<_>>()
+            .len();
+
+        if registry.fds.len() + added > max_fds {
+            return Err(io::Error::other(
+                "reactor has reached its maximum number of registered file descriptors",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Rejects file descriptors which can not be polled on behalf of a caller.
+    fn validate(&self, fd: RawFd) -> io::Result<()> {
+        self.check_fork()?;
+
+        if fd < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file descriptor is negative",
+            ));
+        }
+
+        if self.shared.interrupt.contains(fd) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file descriptor belongs to the reactor",
+            ));
+        }
+
+        if self.shared.require_nonblocking && crate::sys::is_blocking(fd) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file descriptor is not in non-blocking mode",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the reactor belongs to the parent of this forked process, unless it is
+    /// reinitialized for this process.
+    fn check_fork(&self) -> io::Result<()> {
+        if self.shared.pid.load(Ordering::SeqCst) == std::process::id() {
+            return Ok(());
+        }
+
+        // The registry may have been locked by a thread which did not survive the fork.
+        if !self.shared.reinit_after_fork {
+            return Err(io::Error::other(
+                "the reactor belongs to the parent of this forked process",
+            ));
+        }
+
+        self.reinit()
+    }
+
+    /// Recreates the reactor within a child process which was forked from the process that
+    /// last used it, discarding the registrations made by the parent.
+    ///
+    /// Its interrupt and poller are shared with the parent, which they would otherwise wake,
+    /// so they are created anew, and its thread is spawned again by the next registration.
+    /// The parent's registrations belong to tasks of threads which were not copied into the
+    /// child, so they are leaked rather than woken or dropped, which could call into an
+    /// executor whose locks were held by those threads.
+    fn reinit(&self) -> io::Result<()> {
+        let mut lock = self.shared.registry();
+        let pid = std::process::id();
+
+        // Another thread of the child may have reinitialized the reactor first.
+        if self.shared.pid.load(Ordering::SeqCst) == pid {
+            return Ok(());
+        }
+
+        self.shared.interrupt.reopen()?;
+        self.shared.interrupt_pending.store(false, Ordering::SeqCst);
+        let poller = Poller::new(self.shared.interrupt.as_raw_fd(), self.shared.signal_mask)?;
+
+        drop(lock.driver.take());
+        mem::forget(mem::take(&mut *lock));
+        lock.driver = Some(Driver::new(&self.shared, poller));
+        self.shared.pid.store(pid, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn next_token(&self) -> Token {
+        Token(NEXT_TOKEN.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Respawn the background thread if it exited while idle.
+    ///
+    /// Should the thread fail to spawn, its driver is lost along with it, so the reactor is
+    /// shut down, and the error is kept as its [last error](Handle::last_error).
+    fn respawn(&self, registry: &mut Registry) -> io::Result<()> {
+        if self.shared.current_thread {
+            return Ok(());
+        }
+
+        if let Some(driver) = registry.driver.take() {
+            match spawn(self.shared.clone(), driver) {
+                Ok(thread) => registry.thread = Some(thread),
+                Err(why) => {
+                    self.shared.shutdown.store(true, Ordering::SeqCst);
+                    let error = clone_error(&why);
+                    *self
+                        .shared
+                        .error
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner) = Some(why);
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves every registration with [`Interest::ERROR`] once the reactor has failed to
+    /// respawn its thread, returning the error for the caller which caused it.
+    fn abandon(&self, mut lock: MutexGuard<'_, Registry>, why: io::Error) -> io::Error {
+        let mut wakers = Vec::new();
+        lock.remove_all(Interest::ERROR, &mut wakers);
+        lock.release_syncs(&mut wakers);
+        drop(lock);
+
+        wake_all(&self.shared, &mut wakers);
+        why
+    }
+
+    /// Unregister the registration of the file descriptor which the token refers to.
+    ///
+    /// The file descriptor remains registered for as long as other registrations wait on it.
+    ///
+    /// The events of the registration are never signaled once this returns, but its waker
+    /// may still be woken once afterwards, should the reactor have already collected it
+    /// before the registration was removed. Wakers are clones which the reactor owns, so
+    /// such a stale wakeup is only ever spurious.
+    pub fn unregister(&self, fd: RawFd, token: Token) {
+        self.shared.with_registrations(|shared, registry| {
+            registry.remove(fd, token);
+            shared.interrupt();
+        });
+    }
+
+    /// Unregister many registrations at once, locking the registry and interrupting the
+    /// reactor only once for all of them.
+    pub fn unregister_many<I>(&self, registrations: I)
+    where
+        I: IntoIterator<Item = (RawFd, Token)>,
+    {
+        self.shared.with_registrations(|shared, registry| {
+            for (fd, token) in registrations {
+                registry.remove(fd, token);
+            }
+
+            shared.interrupt();
+        });
+    }
+
+    /// Changes the interest of an existing registration in place.
+    ///
+    /// This avoids the window in which the file descriptor would not be polled at all, were
+    /// it to be unregistered and registered again with the new interest.
+    ///
+    /// An empty interest suspends the registration, which is then never woken, and no
+    /// longer polls the file descriptor unless other registrations wait on it, until its
+    /// interest is modified again. File descriptors may also be registered with an empty
+    /// interest, so as to be resumed later.
+    ///
+    /// Events which were already signaled to the registration, but are not among the new
+    /// interest, are [discarded](Completion::retain), so that they do not complete a wait
+    /// on the new interest. Error and hangup conditions, and timeouts, are retained.
+    pub fn modify_interest(&self, fd: RawFd, token: Token, interest: Interest) {
+        self.shared.with_registrations(|shared, registry| {
+            let waiter = registry
+                .fds
+                .get_mut(&fd)
+                .and_then(|waiters| waiters.iter_mut().find(|waiter| waiter.token == token));
+
+            if let Some(waiter) = waiter {
+                waiter.interest = interest;
+                waiter
+                    .ready
+                    .retain(interest | Interest::UNMASKABLE | Interest::TIMEOUT);
+                registry.changed.insert(fd);
+                shared.interrupt();
+            }
+        });
+    }
+
+    /// Replaces the waker of an existing registration in place, returning whether the
+    /// registration remains.
+    ///
+    /// The file descriptor is polled throughout, rather than briefly not at all, were it to
+    /// be unregistered and registered again with the new waker. As the interest is
+    /// unchanged, the reactor's thread is not interrupted either.
+    pub fn update_waker(&self, fd: RawFd, token: Token, waker: Waker) -> bool {
+        self.shared.with_registrations(|_, registry| {
+            let waiter = registry
+                .fds
+                .get_mut(&fd)
+                .and_then(|waiters| waiters.iter_mut().find(|waiter| waiter.token == token));
+
+            match waiter {
+                Some(waiter) => {
+                    waiter.waker = waker;
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    /// Blocks the current thread until the file descriptor is ready, returning the events
+    /// which were observed.
+    ///
+    /// This is for callers which are not running within an async runtime. Error conditions
+    /// on the file descriptor are returned as events, and an error of kind
+    /// [`io::ErrorKind::TimedOut`] is returned if the timeout elapses first.
+    pub fn wait_ready(
+        &self,
+        fd: RawFd,
+        interest: Interest,
+        timeout: Option<Duration>,
+    ) -> io::Result<Interest> {
+        let ready = Arc::new(AtomicI16::new(0));
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+
+        let token = match timeout {
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+                self.register_with_deadline(fd, interest, ready.clone(), waker, deadline)?
+            }
+            None => self.register(fd, interest, ready.clone(), waker)?,
+        };
+
+        // Parking may wake spuriously, so the thread parks until events have been stored.
+        let events = loop {
+            match ready.load(Ordering::SeqCst) {
+                0 => thread::park(),
+                events => break Interest::from_bits_truncate(events),
+            }
+        };
+
+        self.unregister(fd, token);
+
+        if events.contains(Interest::TIMEOUT) {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out waiting for the file descriptor",
+            ));
+        }
+
+        Ok(events)
+    }
+
+    /// Blocks the current thread until the reactor has applied every change to its
+    /// registrations which was made before the call, so that its next wait for events
+    /// polls them.
+    ///
+    /// This lets tests make a file descriptor ready only once the reactor is known to be
+    /// polling it. Returns immediately if the reactor has shut down, or if its loop is not
+    /// running, such as while it is idle or [paused](Handle::pause), in which case the
+    /// changes are applied as soon as it runs again. Must not be called from within the
+    /// loop of a reactor which runs on the current thread, such as from one of its
+    /// callbacks, as it would wait on itself.
+    pub fn sync(&self) {
+        if self.check_fork().is_err() {
+            return;
+        }
+
+        let synced = Arc::new(AtomicBool::new(false));
+
+        let mut lock = self.shared.registry();
+        let running_here = lock
+            .thread
+            .as_ref()
+            .is_some_and(|thread| thread.thread().id() == thread::current().id());
+
+        if lock.driver.is_some() || running_here || self.shared.shutdown.load(Ordering::SeqCst) {
+            return;
+        }
+
+        // A paused loop releases no syncs until it is resumed, which the caller may be the
+        // one to do. Checked while locked, as the reactor is only paused while locked.
+        if self.shared.is_paused() {
+            return;
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        lock.syncs.push((synced.clone(), waker));
+        self.shared.interrupt();
+        drop(lock);
+
+        // Parking may wake spuriously, so the thread parks until it has been released.
+        while !synced.load(Ordering::SeqCst) {
+            thread::park();
+        }
+    }
+
+    /// Pauses the reactor's loop, so that no registration is signaled or woken until the
+    /// loop is [resumed](Handle::resume).
+    ///
+    /// Registrations may still be made and removed while paused, and events which occur in
+    /// the meantime are observed once the loop resumes. Wakers which the loop collected just
+    /// before it was paused may still be woken. A reactor which runs on the current thread
+    /// returns from [`Reactor::step_once`] immediately while paused, and blocks within
+    /// [`Reactor::run_current_thread`] until it is resumed. Pausing a paused reactor has no
+    /// effect, and shutting it down resumes it.
+    pub fn pause(&self) {
+        // Taken so that the loop observes the pause before it next signals registrations.
+        let lock = self.shared.registry();
+        *self
+            .shared
+            .paused
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.shared.interrupt();
+        drop(lock);
+    }
+
+    /// Resumes the reactor's loop after it was [paused](Handle::pause), which then polls
+    /// every registration afresh.
+    pub fn resume(&self) {
+        self.shared.resume();
+    }
+
+    /// Checks which of the interests are ready on the file descriptor right now, without
+    /// registering it or waiting on the reactor's thread.
+    ///
+    /// The returned events are empty if the file descriptor is not yet ready, in which case
+    /// the caller may register it and await readiness as usual.
+    pub fn poll_now(&self, fd: RawFd, interest: Interest) -> io::Result<Interest> {
+        self.validate(fd)?;
+        crate::sys::poll_now(fd, interest)
+    }
+
+    /// The events of the interest which the reactor last observed on the file descriptor,
+    /// and which have not been cleared since, with the
+    /// [readiness cache](ReactorBuilder::readiness_cache).
+    ///
+    /// Empty without the cache, or if the file descriptor refers to another file than the
+    /// one which the events were observed on.
+    pub fn cached_readiness(&self, fd: RawFd, interest: Interest) -> Interest {
+        if !self.shared.readiness_cache {
+            return Interest::empty();
+        }
+
+        let file = crate::sys::file_id(fd);
+        self.shared
+            .with_registrations(|_, registry| match registry.readiness.get(&fd) {
+                Some(readiness) if Some(readiness.file) == file => readiness.events & interest,
+                Some(_) => {
+                    registry.readiness.remove(&fd);
+                    Interest::empty()
+                }
+                None => Interest::empty(),
+            })
+    }
+
+    /// Clears the cached readiness of the events on the file descriptor, once an operation
+    /// on it would block, so that futures wait on the reactor for them again.
+    ///
+    /// Does nothing without the [readiness cache](ReactorBuilder::readiness_cache).
+    pub fn clear_readiness(&self, fd: RawFd, events: Interest) {
+        if !self.shared.readiness_cache {
+            return;
+        }
+
+        self.shared.with_registrations(|_, registry| {
+            if let Some(readiness) = registry.readiness.get_mut(&fd) {
+                readiness.events.remove(events);
+            }
+        });
+    }
+
+    /// A snapshot of every registered file descriptor, with the combined interest of the
+    /// registrations waiting on it.
+    pub fn registered(&self) -> Vec<(RawFd, Interest)> {
+        self.shared.with_registrations(|_, registry| {
+            registry
+                .fds
+                .iter()
+                .map(|(&fd, waiters)| (fd, merged_interest(waiters)))
+                .collect()
+        })
+    }
+
+    /// Whether any registration is waiting on the file descriptor.
+    pub fn is_registered(&self, fd: RawFd) -> bool {
+        self.shared
+            .with_registrations(|_, registry| registry.fds.contains_key(&fd))
+    }
+
+    /// Delivers the events to the registrations of the file descriptor as though the
+    /// reactor had observed them, without waiting for the file descriptor to be ready.
+    ///
+    /// This is for testing code built on registrations without real readiness, and for
+    /// waking registrations on events from outside of the kernel. Registrations are woken
+    /// according to their interest and [mode](Mode), as they would be by the reactor, and
+    /// are found on the reactor which this one was [drained](Reactor::drain_into) into.
+    pub fn force_ready(&self, fd: RawFd, events: Interest) {
+        let mut wakers = Vec::new();
+        let shared = self.shared.with_registrations(|shared, registry| {
+            registry.deliver(fd, events, Instant::now(), &mut wakers, &mut Vec::new());
+
+            // Registrations which were removed must also be removed from the poller.
+            if registry.changed.contains(&fd) {
+                shared.interrupt();
+            }
+
+            shared.clone()
+        });
+
+        wake_all(&shared, &mut wakers);
+    }
+
+    /// When the reactor last found the file descriptor ready for any of its registrations,
+    /// or `None` if it has not been found ready since it was registered.
+    ///
+    /// The time is forgotten once the file descriptor has no more registrations, so
+    /// callers which reap idle file descriptors should keep them registered, such as with
+    /// a [`ReadinessStream`](crate::ReadinessStream).
+    pub fn last_ready(&self, fd: RawFd) -> Option<Instant> {
+        self.shared
+            .with_registrations(|_, registry| registry.last_ready.get(&fd).copied())
+    }
+
+    /// The earliest deadline of any registration, which bounds how long the reactor next
+    /// waits for events.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.shared
+            .with_registrations(|_, registry| registry.next_deadline())
+    }
+
+    /// The combined interest of every registration waiting on the file descriptor, which
+    /// is the interest that the reactor polls it for, or `None` if it is not registered.
+    pub fn effective_interest(&self, fd: RawFd) -> Option<Interest> {
+        self.shared.with_registrations(|_, registry| {
+            registry
+                .fds
+                .get(&fd)
+                .map(|waiters| merged_interest(waiters))
+        })
+    }
+
+    /// How many registrations are waiting on the file descriptor.
+    pub fn waiter_count(&self, fd: RawFd) -> usize {
+        self.shared
+            .with_registrations(|_, registry| registry.fds.get(&fd).map_or(0, Vec::len))
+    }
+
+    /// A snapshot of the reactor's counters, for tuning and diagnostics.
+    pub fn metrics(&self) -> ReactorMetrics {
+        let registered_fds = self.shared.registry().fds.len();
+        self.shared.counters.snapshot(registered_fds)
+    }
+
+    /// Whether the reactor has been shut down.
+    ///
+    /// Futures which were registered when the reactor shut down are woken with
+    /// [`Interest::ERROR`], as are any futures which register afterwards.
+    pub fn is_shutdown(&self) -> bool {
+        self.shared.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// The error which last caused the reactor to fail polling, if it has ever failed.
+    ///
+    /// When polling fails, every registered file descriptor is removed from the reactor and
+    /// its future is woken, so that futures may check for this error once they resolve. The
+    /// same happens when a registration fails to spawn the background thread again, after
+    /// it exited while idle, which also shuts the reactor down.
+    pub fn last_error(&self) -> Option<io::Error> {
+        let error = self
+            .shared
+            .error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        // The error is recreated for each caller, as errors may not be cloned.
+        error.as_ref().map(clone_error)
+    }
+}
+
+/// A registration of a file descriptor, which is unregistered from the reactor once
+/// dropped.
+///
+/// Created with [`Handle::register_guarded`].
+#[derive(Debug)]
+pub struct Registration<'a> {
+    handle: &'a Handle,
+    fd: RawFd,
+    token: Token,
+}
+
+impl<'a> Registration<'a> {
+    pub(crate) fn new(handle: &'a Handle, fd: RawFd, token: Token) -> Self {
+        Self { handle, fd, token }
+    }
+
+    /// The registered file descriptor.
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// The token which identifies the registration.
+    pub fn token(&self) -> Token {
+        self.token
+    }
+
+    /// Changes the interest of the registration in place.
+    pub fn modify_interest(&self, interest: Interest) {
+        self.handle.modify_interest(self.fd, self.token, interest);
+    }
+
+    /// Replaces the waker of the registration in place, returning whether the registration
+    /// remains.
+    pub fn update_waker(&self, waker: Waker) -> bool {
+        self.handle.update_waker(self.fd, self.token, waker)
+    }
+}
+
+impl<'a> Drop for Registration<'a> {
+    fn drop(&mut self) {
+        self.handle.unregister(self.fd, self.token);
+    }
+}
+
+/// Recreates an error, which may not be cloned, with the same kind and description.
+pub(crate) fn clone_error(why: &io::Error) -> io::Error {
+    match why.raw_os_error() {
+        Some(errno) => io::Error::from_raw_os_error(errno),
+        None => io::Error::new(why.kind(), why.to_string()),
+    }
+}
+
+/// The events which a regular file is ready for, which it always is for reading and writing,
+/// or `None` if the file descriptor is not a regular file, or has none of these interests.
+fn always_ready(fd: RawFd, interest: Interest) -> Option<Interest> {
+    Some(interest & Interest::BOTH)
+        .filter(|events| !events.is_empty() && crate::sys::is_regular_file(fd))
+}
+
+/// Wakes a thread which is blocked in [`Handle::wait_ready`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Calls the callback of a [`Handle::register_callback`] registration when it is woken.
+struct Callback {
+    /// The events signaled since the callback was last called.
+    events: AtomicI16,
+    callback: Mutex<Box<dyn FnMut(Interest) + Send>>,
+}
+
+impl Completion for Callback {
+    fn signal(&self, events: Interest) {
+        self.events.signal(events);
+    }
+
+    fn pending(&self) -> Interest {
+        self.events.pending()
+    }
+}
+
+impl Wake for Callback {
+    fn wake(self: Arc<Self>) {
+        let events = Interest::from_bits_truncate(self.events.swap(0, Ordering::SeqCst));
+        if !events.is_empty() {
+            let mut callback = self.callback.lock().unwrap_or_else(PoisonError::into_inner);
+            callback(events);
+        }
+    }
+}
+
+fn spawn(shared: Arc<Shared>, driver: Driver) -> io::Result<JoinHandle<()>> {
+    if let Some(spawner) = shared.spawner.clone() {
+        return (spawner.0)(Box::new(move || run(&shared, driver)));
+    }
+
+    thread::Builder::new()
+        .name(shared.thread_name.clone())
+        .spawn(move || run(&shared, driver))
+}
+
+/// Resolves every registration once the background thread exits, whether it was shut
+/// down or panicked, unless it exited to park while idle.
+///
+/// The reactor is marked as shut down, so that every future which registers afterwards
+/// also resolves with [`Interest::ERROR`] rather than waiting on a thread which is gone.
+struct ExitGuard<'a> {
+    shared: &'a Shared,
+    parked: bool,
+}
+
+impl<'a> Drop for ExitGuard<'a> {
+    fn drop(&mut self) {
+        if self.parked {
+            return;
+        }
+
+        let mut wakers = Vec::new();
+
+        let mut lock = self.shared.registry();
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        lock.remove_all(Interest::ERROR, &mut wakers);
+        lock.release_syncs(&mut wakers);
+        drop(lock);
+
+        wake_all(self.shared, &mut wakers);
+    }
+}
+
+/// The reactor which is [reinitialized](ReactorBuilder::reinit_after_fork) by children
+/// forked from this process.
+static FORKING_REACTOR: OnceCell<Arc<Shared>> = OnceCell::new();
+
+/// The locks of the reactor which are held by the forking thread.
+type ForkGuard = (
+    MutexGuard<'static, Registry>,
+    MutexGuard<'static, bool>,
+    MutexGuard<'static, Option<io::Error>>,
+);
+
+thread_local! {
+    static FORK_GUARD: RefCell<Option<ForkGuard>> = const { RefCell::new(None) };
+}
+
+/// Locks the reactor before the process forks, in the order in which the reactor's thread
+/// takes the same locks, so that the child never copies it while another thread held them.
+extern "C" fn prepare_fork() {
+    if let Some(shared) = FORKING_REACTOR.get() {
+        let guard = (
+            shared.registry(),
+            shared.paused.lock().unwrap_or_else(PoisonError::into_inner),
+            shared.error.lock().unwrap_or_else(PoisonError::into_inner),
+        );
+
+        FORK_GUARD.with(|forking| *forking.borrow_mut() = Some(guard));
+    }
+}
+
+/// Unlocks the reactor once the process has forked, within both the parent and the child.
+extern "C" fn release_fork() {
+    FORK_GUARD.with(|forking| drop(forking.borrow_mut().take()));
+}
+
+/// Wakes every collected waker, counting them towards the reactor's metrics, and returning
+/// how many were woken.
+///
+/// Wakers are cloned while the registry is locked, so registrations which have since been
+/// removed are woken through the reactor's own clones of their wakers.
+///
+/// Wakers are woken in order of the [priority](Handle::register_with_priority) of their
+/// registrations. A task which waits on several file descriptors that became ready together
+/// is woken only once, as each of its wakers shares the same data and vtable.
+fn wake_all(shared: &Shared, wakers: &mut Vec<(u8, Waker)>) -> usize {
+    if wakers.len() > 1 {
+        // Sorted stably, so that the order of registrations of the same priority is kept.
+        if wakers.iter().any(|&(priority, _)| priority != 0) {
+            wakers.sort_by_key(|&(priority, _)| std::cmp::Reverse(priority));
+        }
+
+        let mut distinct = HashSet::with_capacity(wakers.len());
+        wakers.retain(|(_, waker)| distinct.insert((waker.data(), waker.vtable() as *const _)));
+    }
+
+    let woken = wakers.len();
+    Counters::increment(&shared.counters.wakeups, woken as u64);
+    wakers.drain(..).for_each(|(_, waker)| waker.wake());
+    woken
+}
+
+/// The reactor's main loop, which runs until the reactor is shut down, or becomes idle.
+///
+/// Every change to the registry is made while holding its lock, and notifies the interrupt
+/// afterwards. Each iteration drains the interrupt and updates the poller while holding the
+/// same lock, so a change is either made before the lock is taken, and is applied to the
+/// poller before its next wait, or is made after the lock is released, and notifies an
+/// interrupt which that wait will return for. A notification is therefore never consumed
+/// before the change which preceded it is visible, and no change which was visible before
+/// its notification is missing from the next wait.
+///
+/// The file descriptor of a registration may have been closed, and its number reused by
+/// another registration, between two updates of the poller. Pollers apply every changed
+/// file descriptor afresh, rather than comparing it against what they last applied.
+fn run(shared: &Shared, mut driver: Driver) {
+    let mut exit = ExitGuard {
+        shared,
+        parked: false,
+    };
+
+    loop {
+        match driver.step(shared, None) {
+            Step::Continue(_) => (),
+            Step::Exit => return,
+            Step::Park(mut lock) => {
+                // Registrations are unable to arrive while the lock is held, and the next one
+                // will spawn the thread again with this driver.
+                let mut wakers = std::mem::take(&mut driver.wakers);
+                lock.driver = Some(driver);
+                exit.parked = true;
+                drop(lock);
+                wake_all(shared, &mut wakers);
+                return;
+            }
+        }
+    }
+}
+
+/// The state of the reactor's loop, which is kept across its iterations, and while the
+/// background thread is parked.
+struct Driver {
+    poller: Poller,
+    events: Vec<(RawFd, Interest)>,
+    wakers: Vec<(u8, Waker)>,
+    fired: Vec<Token>,
+    deadline: Option<Instant>,
+    idle_since: Option<Instant>,
+    rotation: usize,
+    window_end: Option<Instant>,
+    replenish: Option<Instant>,
+}
+
+/// How the loop proceeds after an iteration.
+enum Step<'a> {
+    /// The loop polls again.
+    Continue(StepReport),
+
+    /// The reactor was shut down.
+    Exit,
+
+    /// The reactor became idle, and its driver is to be stored in the registry before its
+    /// lock is released.
+    Park(MutexGuard<'a, Registry>),
+}
+
+impl Driver {
+    fn new(shared: &Shared, poller: Poller) -> Self {
+        let now = Instant::now();
+
+        Self {
+            poller,
+            events: Vec::new(),
+            wakers: Vec::new(),
+            fired: Vec::new(),
+            deadline: None,
+            idle_since: Some(now),
+            rotation: 0,
+            window_end: shared.wakeup_budget.map(|(_, window)| now + window),
+            replenish: None,
+        }
+    }
+
+    /// Polls once, waiting no longer than `limit` if given, and wakes the registrations
+    /// whose events were observed.
+    fn step<'a>(&mut self, shared: &'a Shared, limit: Option<Duration>) -> Step<'a> {
+        let mut report = StepReport::default();
+
+        // Checked before every wait, as the reactor may have been shut down after the last
+        // iteration checked, yet before it cleared a notification which was already pending,
+        // in which case shutting down never notified the interrupt again.
+        if shared.shutdown.load(Ordering::SeqCst) {
+            return Step::Exit;
+        }
+
+        let idle_deadline = self
+            .idle_since
+            .zip(shared.idle_timeout)
+            .map(|(since, timeout)| since + timeout);
+
+        let timeout = [self.deadline, idle_deadline, self.replenish]
+            .iter()
+            .flatten()
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+        let timeout = [timeout, shared.maintenance_interval, limit]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+
+        #[cfg(feature = "tracing")]
+        let waiting = Instant::now();
+
+        let result = self.poller.wait(&mut self.events, timeout);
+        let now = Instant::now();
+
+        #[cfg(feature = "tracing")]
+        let ready = self.events.len();
+        Counters::increment(&shared.counters.poll_iterations, 1);
+        let mut lock = shared.registry();
+
+        // Every future is woken by the exit guard.
+        if shared.shutdown.load(Ordering::SeqCst) {
+            return Step::Exit;
+        }
+
+        // The events are discarded, and observed afresh once the loop resumes, as is every
+        // registration which arrives in the meantime.
+        if shared.is_paused() {
+            self.events.clear();
+            drop(lock);
+            shared.wait_resumed();
+
+            // Edge-triggered file descriptors are armed again, so that events which they
+            // reported while paused are reported again.
+            let mut lock = shared.registry();
+            let fds = lock.fds.keys().copied().collect::<Vec<_>>();
+            lock.changed.extend(fds);
+            self.poller.update(&mut lock);
+            return Step::Continue(report);
+        }
+
+        if let Err(why) = result {
+            // A signal interrupted the poll, which can simply be retried.
+            if why.kind() == io::ErrorKind::Interrupted {
+                return Step::Continue(report);
+            }
+
+            // Wake every future so that none waits on a reactor that failed to poll it.
+            *shared.error.lock().unwrap_or_else(PoisonError::into_inner) = Some(why);
+
+            lock.remove_all(Interest::ERROR, &mut self.wakers);
+        } else {
+            let interrupt = shared.interrupt.as_raw_fd();
+
+            // Pollers report events in much the same order on every wait, so the events are
+            // rotated by one more on each iteration, so that the same wakers are not always
+            // the first to be woken when many file descriptors are ready at once.
+            if !self.events.is_empty() {
+                let len = self.events.len();
+                self.events.rotate_left(self.rotation % len);
+                self.rotation = self.rotation.wrapping_add(1);
+            }
+
+            for &(fd, events) in &self.events {
+                // The interrupt may be reported alongside other events, and in the same poll
+                // as other file descriptors, which must still be handled.
+                if fd == interrupt {
+                    // A closed interrupt may never wake the thread again.
+                    // Registrations which were already delivered by this poll are woken
+                    // here, as the exit guard only wakes those which remain registered.
+                    if events.intersects(Interest::UNMASKABLE) {
+                        shared.shutdown.store(true, Ordering::SeqCst);
+                        drop(lock);
+                        wake_all(shared, &mut self.wakers);
+                        return Step::Exit;
+                    }
+
+                    if events.is_readable() {
+                        // Cleared only after draining, and while the registry is locked, so
+                        // that a notification is never consumed without clearing its flag,
+                        // and every change made afterwards notifies the interrupt again.
+                        shared.interrupt.drain();
+                        shared.interrupt_pending.store(false, Ordering::SeqCst);
+                    }
+
+                    continue;
+                }
+
+                report.ready += 1;
+
+                // A closed file descriptor would be reported on every poll until its futures
+                // unregister it, so it is removed immediately.
+                if events.contains(Interest::INVALID) {
+                    lock.remove_fd(fd, events, &mut self.wakers);
+                    report.matched += 1;
+                    continue;
+                }
+
+                if !lock.deliver(fd, events, now, &mut self.wakers, &mut self.fired) {
+                    Counters::increment(&shared.counters.spurious_wakeups, 1);
+                    continue;
+                }
+
+                report.matched += 1;
+
+                if let Some((budget, _)) = shared.wakeup_budget {
+                    lock.spend(fd, budget);
+                }
+            }
+        }
+
+        lock.expire(now, &mut self.wakers);
+
+        if let (Some(end), Some((_, window))) = (self.window_end, shared.wakeup_budget) {
+            if end <= now {
+                lock.replenish();
+                self.window_end = Some(now + window);
+            }
+        }
+
+        // Deferred file descriptors are only polled again once the budget window ends.
+        self.replenish = self.window_end.filter(|_| !lock.deferred.is_empty());
+        self.deadline = lock.next_deadline();
+
+        self.events.clear();
+
+        if !lock.fds.is_empty() {
+            self.idle_since = None;
+        } else if self.idle_since.is_none() || !lock.changed.is_empty() {
+            self.idle_since = Some(now);
+        } else if idle_deadline.is_some_and(|idle_deadline| idle_deadline <= now) {
+            self.poller.update(&mut lock);
+            lock.release_syncs(&mut self.wakers);
+            return Step::Park(lock);
+        }
+
+        self.poller.update(&mut lock);
+        lock.release_syncs(&mut self.wakers);
+
+        // Wakers may poll their task inline, which could re-enter the reactor, so they
+        // are only invoked after the lock has been released.
+        drop(lock);
+
+        report.woken = wake_all(shared, &mut self.wakers);
+
+        #[cfg(feature = "tracing")]
+        trace_iteration(shared, now - waiting, now.elapsed(), ready, report.woken);
+
+        Step::Continue(report)
+    }
+}
+
+/// Records how long an iteration of the loop waited for events, and how long it then took
+/// to wake registrations, warning of iterations which were slower than the threshold.
+#[cfg(feature = "tracing")]
+fn trace_iteration(shared: &Shared, waited: Duration, woke: Duration, ready: usize, woken: usize) {
+    if woke >= shared.slow_threshold {
+        tracing::warn!(
+            ?waited,
+            ?woke,
+            ready,
+            woken,
+            "fd-reactor was slow to wake its registrations"
+        );
+    } else {
+        tracing::trace!(?waited, ?woke, ready, woken, "fd-reactor polled");
+    }
+}
+
+#[cfg(all(
+    test,
+    any(
+        target_os = "linux",
+        all(
+            any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ),
+            feature = "kqueue"
+        )
+    )
+))]
+impl Registry {
+    /// A registry holding a registration of each file descriptor, for testing pollers
+    /// without a reactor.
+    pub(crate) fn with_interests(interests: &[(RawFd, Interest)]) -> Self {
+        let mut registry = Self::default();
+        for (token, &(fd, interest)) in interests.iter().enumerate() {
+            registry.fds.entry(fd).or_default().push(Waiter {
+                token: Token(token as u64),
+                interest,
+                ready: Box::new(Arc::new(AtomicI16::new(0))),
+                waker: crate::test_util::noop_waker(),
+                mode: Mode::Level,
+                deadline: None,
+                owned: None,
+                priority: 0,
+            });
+            registry.changed.insert(fd);
+        }
+
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,2151 @@
+use super::*;
+use crate::{test_util::*, FdFuture, InlineFdFuture, REACTOR};
+use std::{
+    future::Future,
+    os::unix::io::AsFd,
+    pin::Pin,
+    sync::mpsc,
+    task::{Context, Wake},
+};
+
+/// Wakers which poll their task inline may register and unregister from within the reactor's
+/// thread, as they are only woken once the registry is no longer locked.
+#[test]
+fn wakers_may_reenter_the_reactor() {
+    struct Reentrant {
+        handle: Arc<Handle>,
+        fd: RawFd,
+        woken: mpsc::Sender<()>,
+    }
+
+    impl Wake for Reentrant {
+        fn wake(self: Arc<Self>) {
+            let token = self
+                .handle
+                .register(
+                    self.fd,
+                    Interest::READ,
+                    Arc::new(AtomicI16::new(0)),
+                    noop_waker(),
+                )
+                .unwrap();
+            self.handle.unregister(self.fd, token);
+            let _ = self.woken.send(());
+        }
+    }
+
+    within(|| {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let handle = Arc::new(handle);
+        let ((reader, writer), (other, _other)) = (pipe(), pipe());
+        let (woken, receiver) = mpsc::channel();
+
+        let waker = Waker::from(Arc::new(Reentrant {
+            handle: handle.clone(),
+            fd: other.as_raw_fd(),
+            woken,
+        }));
+
+        let fd = reader.as_raw_fd();
+        let token = handle
+            .register(fd, Interest::READ, Arc::new(AtomicI16::new(0)), waker)
+            .unwrap();
+        write_byte(&writer);
+
+        receiver.recv().unwrap();
+        handle.unregister(fd, token);
+    });
+}
+
+/// A storm of registrations from many threads never blocks on notifying the interrupt,
+/// however many notifications are written before the reactor drains them.
+#[test]
+fn registration_storms_never_block() {
+    const THREADS: usize = 8;
+    const REGISTRATIONS: usize = 100_000;
+
+    within(|| {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (reader, _writer) = pipe();
+        let fd = reader.as_raw_fd();
+
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..REGISTRATIONS / THREADS {
+                        let token = handle
+                            .register(
+                                fd,
+                                Interest::READ,
+                                Arc::new(AtomicI16::new(0)),
+                                noop_waker(),
+                            )
+                            .unwrap();
+                        handle.unregister(fd, token);
+                    }
+                });
+            }
+        });
+    });
+}
+
+/// Notifying the interrupt never opens a file descriptor, so registrations are still picked
+/// up by the reactor once the process has no file descriptors to spare.
+#[test]
+fn registrations_never_open_file_descriptors() {
+    if !in_child_process("reactor::tests::registrations_never_open_file_descriptors") {
+        return;
+    }
+
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    write_byte(&writer);
+    exhaust_open_files();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let token = handle
+        .register(
+            reader.as_raw_fd(),
+            Interest::READ,
+            ready.clone(),
+            noop_waker(),
+        )
+        .unwrap();
+    wait_until(|| ready.load(Ordering::SeqCst) != 0);
+    handle.unregister(reader.as_raw_fd(), token);
+
+    // Futures register and resolve through the reactor as usual.
+    let future = FdFuture::new_with(&handle, reader.as_raw_fd(), Interest::READ);
+    assert_eq!(block_on(future), Interest::READ);
+    handle.sync();
+    assert!(handle.registered().is_empty());
+    assert!(handle.last_error().is_none() && !handle.is_shutdown());
+}
+
+/// An interrupt which can no longer be notified shuts the reactor down, rather than failing
+/// the registration which notified it, and the registration resolves once the loop exits.
+#[cfg(target_os = "linux")]
+#[test]
+fn closed_interrupts_shut_the_reactor_down() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let ((reader, _writer), (unwritable, _unwritable)) = (pipe(), pipe());
+
+    // Replaced with the read end of a pipe, which fails every write with `EBADF`.
+    let interrupt = handle.shared.interrupt.as_raw_fd();
+    assert_ne!(unsafe { libc::dup2(unwritable.as_raw_fd(), interrupt) }, -1);
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let counter = Arc::new(CountingWaker::default());
+    let waker = Waker::from(counter.clone());
+    handle
+        .register(reader.as_raw_fd(), Interest::READ, ready.clone(), waker)
+        .unwrap();
+    assert!(handle.is_shutdown());
+
+    let report = reactor.step_once(Some(TIMEOUT)).unwrap();
+    assert_eq!(report, StepReport::default());
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::ERROR.bits());
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert!(handle.registered().is_empty());
+
+    let future = FdFuture::new_with(&handle, reader.as_raw_fd(), Interest::READ);
+    assert_eq!(block_on(future), Interest::ERROR);
+}
+
+/// Stopping the reactor while other threads keep notifying its interrupt always joins its
+/// thread, however the shutdown interleaves with the thread clearing a notification.
+#[test]
+fn stopping_while_interrupted_never_hangs() {
+    let (reader, _writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    for _ in 0..200 {
+        let (reactor, handle) = Reactor::new().unwrap();
+        let (handle, stopped) = (Arc::new(handle), Arc::new(AtomicBool::new(false)));
+
+        let threads = (0..4)
+            .map(|_| {
+                let (handle, stopped) = (handle.clone(), stopped.clone());
+                thread::spawn(move || {
+                    while !stopped.load(Ordering::SeqCst) {
+                        let ready = Arc::new(AtomicI16::new(0));
+                        if let Ok(token) = handle.register(fd, Interest::READ, ready, noop_waker())
+                        {
+                            handle.unregister(fd, token);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        within(move || reactor.shutdown());
+        stopped.store(true, Ordering::SeqCst);
+        threads
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+    }
+}
+
+/// Registrations which are made and removed on the same file descriptors from many threads,
+/// while the reactor is mid-iteration, are each polled by its next wait.
+#[test]
+fn concurrent_registrations_are_never_missed() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let handle = Arc::new(handle);
+
+    let pipes = (0..4).map(|_| pipe()).collect::<Vec<_>>();
+    pipes.iter().for_each(|(_, writer)| write_byte(writer));
+    let fds = pipes
+        .iter()
+        .map(|(reader, _)| reader.as_raw_fd())
+        .collect::<Vec<_>>();
+
+    let threads = (0..8)
+        .map(|thread| {
+            let (handle, fds) = (handle.clone(), fds.clone());
+            thread::spawn(move || {
+                for iteration in 0..500 {
+                    let fd = fds[(thread + iteration) % fds.len()];
+
+                    if iteration % 2 == 0 {
+                        let ready = Arc::new(AtomicI16::new(0));
+                        let token = handle
+                            .register(fd, Interest::READ, ready, noop_waker())
+                            .unwrap();
+                        handle.unregister(fd, token);
+                    } else {
+                        // Hangs until the timeout should the registration have been missed.
+                        let events = handle.wait_ready(fd, Interest::READ, Some(TIMEOUT));
+                        assert!(events.unwrap().is_readable());
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+    assert!(handle.registered().is_empty());
+}
+
+/// One-shot registrations which were delivered in the same poll as a hangup of the interrupt
+/// are still woken, though they are no longer registered once the loop exits.
+///
+/// The pipes become ready, and the interrupt hangs up, while the reactor's thread is blocked
+/// in a waker, so that the next poll reports all of them at once. The poll backend reports
+/// them in an order which rotates on each iteration, and the pipes are woken either way.
+#[cfg(not(any(feature = "epoll", feature = "kqueue")))]
+#[test]
+fn interrupt_hangups_wake_delivered_registrations() {
+    struct Blocking {
+        entered: mpsc::Sender<()>,
+        release: Mutex<mpsc::Receiver<()>>,
+    }
+
+    impl Wake for Blocking {
+        fn wake(self: Arc<Self>) {
+            let _ = self.entered.send(());
+            let _ = self.release.lock().unwrap().recv();
+        }
+    }
+
+    let (_reactor, handle) = Reactor::new().unwrap();
+
+    let pipes = (0..2).map(|_| pipe()).collect::<Vec<_>>();
+    let counters = pipes
+        .iter()
+        .map(|(reader, _)| {
+            let counter = Arc::new(CountingWaker::default());
+            let (fd, ready) = (reader.as_raw_fd(), Arc::new(AtomicI16::new(0)));
+            let waker = Waker::from(counter.clone());
+            handle
+                .register_with_mode(fd, Interest::READ, Mode::OneShot, ready, waker)
+                .unwrap();
+            counter
+        })
+        .collect::<Vec<_>>();
+
+    let (entered, entered_receiver) = mpsc::channel();
+    let (release, release_receiver) = mpsc::channel();
+    let blocking = Arc::new(Blocking {
+        entered,
+        release: Mutex::new(release_receiver),
+    });
+
+    let (blocker, blocker_writer) = pipe();
+    let ready = Arc::new(AtomicI16::new(0));
+    handle
+        .register_with_mode(
+            blocker.as_raw_fd(),
+            Interest::READ,
+            Mode::OneShot,
+            ready,
+            Waker::from(blocking),
+        )
+        .unwrap();
+    write_byte(&blocker_writer);
+    entered_receiver.recv_timeout(TIMEOUT).unwrap();
+
+    // A pipe whose writer was closed reports a hangup in place of the interrupt.
+    pipes.iter().for_each(|(_, writer)| write_byte(writer));
+    let (hungup, _) = pipe();
+    let interrupt = handle.shared.interrupt.as_raw_fd();
+    assert_ne!(unsafe { libc::dup2(hungup.as_raw_fd(), interrupt) }, -1);
+    release.send(()).unwrap();
+
+    wait_until(|| handle.is_shutdown());
+    for counter in counters {
+        wait_until(|| counter.0.load(Ordering::SeqCst) == 1);
+    }
+}
+
+/// A file descriptor which is registered on two reactors is polled by each independently,
+/// and the registrations of one are unaffected by those of the other.
+#[test]
+fn reactors_share_file_descriptors_independently() {
+    let (_first, reading) = Reactor::new().unwrap();
+    let (_second, writing) = Reactor::new().unwrap();
+
+    let (socket, peer) = socketpair();
+    let fd = socket.as_raw_fd();
+
+    // Edge-triggered, so that it remains registered once woken.
+    let ready = Arc::new(AtomicI16::new(0));
+    let token = reading
+        .register_with_mode(fd, Interest::READ, Mode::Edge, ready.clone(), noop_waker())
+        .unwrap();
+
+    // The socket is writable from the start, while nothing has been written to read.
+    let events = writing.wait_ready(fd, Interest::WRITE, Some(TIMEOUT));
+    assert!(events.unwrap().is_writable());
+    assert!(!writing.is_registered(fd));
+    assert_eq!(reading.waiter_count(fd), 1);
+    assert_eq!(ready.load(Ordering::SeqCst), 0);
+
+    write_byte(&peer);
+    wait_until(|| ready.load(Ordering::SeqCst) != 0);
+    assert!(Interest::from_bits_truncate(ready.load(Ordering::SeqCst)).is_readable());
+
+    let events = writing.wait_ready(fd, Interest::WRITE, Some(TIMEOUT));
+    assert!(events.unwrap().is_writable());
+    assert_eq!(reading.waiter_count(fd), 1);
+
+    reading.unregister(fd, token);
+    assert!(reading.registered().is_empty() && writing.registered().is_empty());
+}
+
+/// Registrations which are unregistered while the reactor delivers events to them may still
+/// be woken, through the waker it cloned before they were removed, yet are woken no more
+/// often than their mode allows, which is once for these.
+#[test]
+fn unregistering_during_delivery_is_harmless() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+
+    // Remains readable throughout, so that the reactor delivers to every registration.
+    let (reader, writer) = pipe();
+    write_byte(&writer);
+    let fd = reader.as_raw_fd();
+
+    let threads = (0..8)
+        .map(|thread| {
+            let handle = handle.clone();
+            thread::spawn(move || {
+                let counter = Arc::new(CountingWaker::default());
+
+                for iteration in 0..1000 {
+                    let mode = [Mode::OneShot, Mode::Edge][(thread + iteration) % 2];
+                    let ready = Arc::new(AtomicI16::new(0));
+                    let waker = Waker::from(counter.clone());
+                    let token = handle
+                        .register_with_mode(fd, Interest::READ, mode, ready, waker)
+                        .unwrap();
+
+                    if iteration % 3 == 0 {
+                        thread::yield_now();
+                    }
+
+                    handle.unregister(fd, token);
+                }
+
+                counter.0.load(Ordering::SeqCst)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        assert!(thread.join().unwrap() <= 1000);
+    }
+
+    assert_eq!(handle.waiter_count(fd), 0);
+    assert!(handle.metrics().wakeups > 0);
+}
+
+/// Once `sync` returns, the reactor has completed an iteration which applied the
+/// registration, so readiness which arrives afterwards is observed by its next wait.
+#[test]
+fn sync_waits_for_registrations_to_be_applied() {
+    let (reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let iterations = handle.metrics().poll_iterations;
+    handle
+        .register(
+            reader.as_raw_fd(),
+            Interest::READ,
+            ready.clone(),
+            noop_waker(),
+        )
+        .unwrap();
+
+    within({
+        let handle = handle.clone();
+        move || handle.sync()
+    });
+
+    assert!(handle.metrics().poll_iterations > iterations);
+    assert_eq!(ready.load(Ordering::SeqCst), 0);
+
+    write_byte(&writer);
+    wait_until(|| ready.load(Ordering::SeqCst) != 0);
+
+    // Returns immediately once there is no loop left to wait on.
+    reactor.shutdown();
+    within(move || handle.sync());
+}
+
+/// Events which no registration is interested in, as the interest was changed while the
+/// reactor waited, are counted as spurious wakeups.
+#[test]
+fn uninterested_events_are_spurious() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+    write_byte(&writer);
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let token = handle
+        .register(fd, Interest::READ, ready, noop_waker())
+        .unwrap();
+
+    // Changed once the poller was updated, yet before it waits, as though the change had
+    // been made while the reactor's thread was waiting.
+    let mut driver = reactor.take_driver().unwrap().unwrap();
+    driver.poller.update(&mut handle.shared.registry());
+    handle.modify_interest(fd, token, Interest::WRITE);
+
+    match driver.step(&handle.shared, Some(TIMEOUT)) {
+        Step::Continue(report) => assert_eq!((report.ready, report.matched), (1, 0)),
+        _ => panic!("the reactor stopped"),
+    }
+
+    handle.shared.registry().driver = Some(driver);
+    assert_eq!(handle.metrics().spurious_wakeups, 1);
+}
+
+/// A child process which was forked after the global reactor was started reinitializes it,
+/// rather than registering onto the parent's, and the parent's reactor keeps working.
+#[test]
+fn forked_children_reinitialize_the_global_reactor() {
+    let wait_for_pipe = || {
+        let (reader, writer) = pipe();
+        let write = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            write_byte(&writer);
+        });
+
+        let events = REACTOR.wait_ready(reader.as_raw_fd(), Interest::READ, Some(TIMEOUT));
+        write.join().unwrap();
+        events.is_ok_and(Interest::is_readable)
+    };
+
+    assert!(wait_for_pipe());
+
+    let child = unsafe { libc::fork() };
+    assert_ne!(child, -1, "fork: {}", io::Error::last_os_error());
+
+    if child == 0 {
+        // The child exits without returning into the test harness, whose other threads
+        // were not copied into it.
+        let woken = std::panic::catch_unwind(wait_for_pipe).unwrap_or(false);
+        unsafe { libc::_exit(if woken { 0 } else { 1 }) };
+    }
+
+    let mut status = 0;
+    assert_eq!(unsafe { libc::waitpid(child, &mut status, 0) }, child);
+    assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+    assert!(wait_for_pipe());
+}
+
+/// Nothing is woken while the reactor is paused, and once it resumes, every registration is
+/// woken by the events which arrived meanwhile, including those registered while paused.
+#[test]
+fn paused_reactors_wake_once_resumed() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let ((before, before_writer), (during, during_writer)) = (pipe(), pipe());
+
+    let register = |fd: &OwnedFd| {
+        let ready = Arc::new(AtomicI16::new(0));
+        handle
+            .register(fd.as_raw_fd(), Interest::READ, ready.clone(), noop_waker())
+            .unwrap();
+        ready
+    };
+
+    let registered_before = register(&before);
+    handle.sync();
+    handle.pause();
+    let ready = [registered_before, register(&during)];
+
+    write_byte(&before_writer);
+    write_byte(&during_writer);
+
+    thread::sleep(Duration::from_millis(50));
+    assert!(ready.iter().all(|ready| ready.load(Ordering::SeqCst) == 0));
+
+    handle.resume();
+    wait_until(|| ready.iter().all(|ready| ready.load(Ordering::SeqCst) != 0));
+}
+
+/// Syncing with a paused reactor returns rather than waiting on its loop, which would only
+/// release it once resumed, such as by the thread which is syncing.
+#[test]
+fn paused_reactors_are_synced_without_waiting() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, _writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    handle.pause();
+    let token = handle
+        .register(
+            fd,
+            Interest::READ,
+            Arc::new(AtomicI16::new(0)),
+            noop_waker(),
+        )
+        .unwrap();
+    let handle = within(move || {
+        handle.sync();
+        handle
+    });
+
+    handle.resume();
+    handle.sync();
+    handle.unregister(fd, token);
+}
+
+/// Registrations which are moved onto another reactor keep their tokens, so that they are
+/// still unregistered and updated through handles to the drained reactor, and pending
+/// futures complete on the other reactor as though they had never moved.
+#[test]
+fn drained_registrations_keep_their_identity() {
+    let (drained, from) = Reactor::new().unwrap();
+    let (_reactor, into) = Reactor::new().unwrap();
+    let ((first, first_writer), (second, _second), (third, _third)) = (pipe(), pipe(), pipe());
+    let fds = [first.as_raw_fd(), second.as_raw_fd(), third.as_raw_fd()];
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut moved = FdFuture::new_with(&from, fds[0], Interest::READ);
+    let mut inline = Box::pin(InlineFdFuture::new_with(&from, fds[1], Interest::READ));
+    assert!(Pin::new(&mut moved).poll(&mut cx).is_pending());
+    assert!(inline.as_mut().poll(&mut cx).is_pending());
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let (mode, waker) = (Mode::Edge, noop_waker());
+    let token = from
+        .register_with_mode(fds[2], Interest::READ, mode, ready, waker)
+        .unwrap();
+
+    drained.drain_into(&into).unwrap();
+    assert!(drained.shared.registry().fds.is_empty());
+    assert!(fds.iter().all(|&fd| into.waiter_count(fd) == 1));
+
+    // Queried through the drained reactor's handle, from the reactor now holding them.
+    let mut registered = from.registered();
+    registered.sort_unstable();
+    let mut expected = fds.map(|fd| (fd, Interest::READ));
+    expected.sort_unstable();
+    assert_eq!(registered, expected);
+    assert!(fds.iter().all(|&fd| from.waiter_count(fd) == 1));
+
+    // Unregistered through the drained reactor's handle, from the reactor now holding them.
+    drop(inline);
+    from.unregister(fds[2], token);
+    assert!(!into.is_registered(fds[1]) && !into.is_registered(fds[2]));
+
+    // Woken by another waker without registering again onto the drained reactor.
+    write_byte(&first_writer);
+    assert!(block_on(&mut moved).is_readable());
+    drop(moved);
+    assert!(into.registered().is_empty());
+}
+
+/// A pipe whose writer was closed wakes the futures waiting to read from it with a hangup,
+/// rather than leaving them waiting for data which never arrives.
+#[test]
+fn hangups_resolve_futures() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = FdFuture::new_with(&handle, fd, Interest::READ);
+    assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+
+    drop(writer);
+    assert!(block_on(future).contains(Interest::HANGUP));
+    assert!(!handle.is_registered(fd));
+}
+
+/// Signals which interrupt the reactor's wait are retried rather than failing the reactor,
+/// whose futures still resolve afterwards.
+#[test]
+fn interrupted_waits_are_retried() {
+    use std::os::unix::thread::JoinHandleExt;
+
+    if !in_child_process("reactor::tests::interrupted_waits_are_retried") {
+        return;
+    }
+
+    ignore_signal(libc::SIGUSR2);
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    handle
+        .register(fd, Interest::READ, ready.clone(), noop_waker())
+        .unwrap();
+    handle.sync();
+
+    let thread = handle
+        .shared
+        .registry()
+        .thread
+        .as_ref()
+        .unwrap()
+        .as_pthread_t();
+    for _ in 0..10 {
+        assert_eq!(unsafe { libc::pthread_kill(thread, libc::SIGUSR2) }, 0);
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    write_byte(&writer);
+    wait_until(|| ready.load(Ordering::SeqCst) != 0);
+    assert!(handle.last_error().is_none() && !handle.is_shutdown());
+}
+
+/// Futures which wait on the same file descriptor are each registered, and each resolved
+/// once it is ready.
+#[test]
+fn futures_share_file_descriptors() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut futures = [
+        FdFuture::new_with(&handle, fd, Interest::READ),
+        FdFuture::new_with(&handle, fd, Interest::READ),
+    ];
+    for future in &mut futures {
+        assert!(Pin::new(future).poll(&mut cx).is_pending());
+    }
+    assert_eq!(handle.waiter_count(fd), 2);
+
+    write_byte(&writer);
+    for future in futures {
+        assert!(block_on(future).is_readable());
+    }
+    assert!(!handle.is_registered(fd));
+}
+
+/// Shutting one reactor down resolves only its own registrations, and leaves those of
+/// another reactor waiting on the same file descriptor registered and working.
+#[test]
+fn reactors_shut_down_independently() {
+    let (stopped, first) = Reactor::new().unwrap();
+    let (_reactor, second) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let register = |handle: &Handle| {
+        let ready = Arc::new(AtomicI16::new(0));
+        handle
+            .register(fd, Interest::READ, ready.clone(), noop_waker())
+            .unwrap();
+        ready
+    };
+
+    let (on_stopped, on_running) = (register(&first), register(&second));
+    stopped.shutdown();
+    assert_eq!(on_stopped.load(Ordering::SeqCst), Interest::ERROR.bits());
+    assert!(!second.is_shutdown() && second.is_registered(fd));
+    assert_eq!(on_running.load(Ordering::SeqCst), 0);
+
+    write_byte(&writer);
+    wait_until(|| on_running.load(Ordering::SeqCst) == Interest::READ.bits());
+}
+
+/// Dropping a reactor joins its thread promptly, and wakes its pending registrations with
+/// an error rather than leaving them waiting forever.
+#[test]
+fn dropping_reactors_wakes_pending_registrations() {
+    let (reactor, handle) = Reactor::new().unwrap();
+    let (reader, _writer) = pipe();
+
+    let counter = Arc::new(CountingWaker::default());
+    let ready = Arc::new(AtomicI16::new(0));
+    let waker = Waker::from(counter.clone());
+    handle
+        .register(reader.as_raw_fd(), Interest::READ, ready.clone(), waker)
+        .unwrap();
+    handle.sync();
+
+    within(move || drop(reactor));
+    assert!(handle.is_shutdown());
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::ERROR.bits());
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert!(handle.registered().is_empty());
+}
+
+/// Modifying the interest of a registration polls it for the new interest, without
+/// registering it again.
+#[test]
+fn modified_interests_are_polled() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (socket, _peer) = socketpair();
+    let fd = socket.as_raw_fd();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let token = handle
+        .register(fd, Interest::READ, ready.clone(), noop_waker())
+        .unwrap();
+    handle.sync();
+    assert_eq!(ready.load(Ordering::SeqCst), 0);
+
+    handle.modify_interest(fd, token, Interest::WRITE);
+    assert_eq!(handle.effective_interest(fd), Some(Interest::WRITE));
+    wait_until(|| ready.load(Ordering::SeqCst) == Interest::WRITE.bits());
+    handle.unregister(fd, token);
+}
+
+/// A reactor which is blocked waiting without a timeout is woken promptly through its
+/// eventfd to poll a new registration.
+#[cfg(target_os = "linux")]
+#[test]
+fn eventfd_interrupts_wake_promptly() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let interrupt = handle.shared.interrupt.as_raw_fd();
+    let link = std::fs::read_link(format!("/proc/self/fd/{}", interrupt)).unwrap();
+    assert_eq!(link.to_str(), Some("anon_inode:[eventfd]"));
+
+    // Blocked in a wait which only the interrupt may end.
+    handle.sync();
+    thread::sleep(Duration::from_millis(10));
+
+    let (reader, writer) = pipe();
+    write_byte(&writer);
+    let started = Instant::now();
+    let events = handle.wait_ready(reader.as_raw_fd(), Interest::READ, None);
+    assert!(events.unwrap().is_readable());
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+/// The interrupt is closed on exec, so that child processes never inherit it, whose writes
+/// would otherwise keep waking the reactor.
+#[cfg(target_os = "linux")]
+#[test]
+fn interrupts_are_not_inherited() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let interrupt = handle.shared.interrupt.as_raw_fd();
+
+    let inherited = format!("[ -e /proc/self/fd/{} ]", interrupt);
+    let status = std::process::Command::new("sh")
+        .args(["-c", &inherited])
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(1));
+}
+
+/// A thread which panics while holding the registry's lock poisons it, which neither the
+/// reactor's thread nor later registrations are affected by.
+#[test]
+fn poisoned_registries_keep_working() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+
+    let shared = handle.shared.clone();
+    let panicked = thread::spawn(move || {
+        let _lock = shared.registry();
+        panic!("poisoning the registry");
+    });
+    assert!(panicked.join().is_err());
+    assert!(handle.shared.registry.is_poisoned());
+
+    let (reader, writer) = pipe();
+    write_byte(&writer);
+    let events = handle.wait_ready(reader.as_raw_fd(), Interest::READ, Some(TIMEOUT));
+    assert!(events.unwrap().is_readable());
+    assert!(handle.registered().is_empty());
+}
+
+/// The thread of an idle reactor exits, and is spawned again by the next registration,
+/// which is then polled as usual.
+#[test]
+fn idle_reactors_respawn_their_thread() {
+    let (_reactor, handle) = Reactor::builder()
+        .idle_timeout(Duration::from_millis(10))
+        .build()
+        .unwrap();
+
+    // Parked once its thread stored the driver back into the registry.
+    wait_until(|| handle.shared.registry().driver.is_some());
+    let parked = handle.shared.registry().thread.take().unwrap();
+    within(move || parked.join().unwrap());
+
+    let (reader, writer) = pipe();
+    write_byte(&writer);
+    let events = handle.wait_ready(reader.as_raw_fd(), Interest::READ, Some(TIMEOUT));
+    assert!(events.unwrap().is_readable());
+    assert!(handle.shared.registry().thread.is_some());
+}
+
+/// A registered file descriptor which is closed resolves its futures with
+/// [`Interest::INVALID`], and is removed from the reactor rather than polled forever.
+#[test]
+fn closed_file_descriptors_are_removed() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, _writer) = pipe();
+
+    // Far above the numbers which other tests open, so that none reuses it once closed.
+    let fd = unsafe { libc::fcntl(reader.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 950) };
+    assert_ne!(fd, -1, "fcntl: {}", io::Error::last_os_error());
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = FdFuture::new_with(&handle, fd, Interest::READ);
+    assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+    handle.sync();
+
+    unsafe { libc::close(fd) };
+    handle.sync();
+    assert!(block_on(future).contains(Interest::INVALID));
+    assert!(!handle.is_registered(fd));
+}
+
+/// The interrupt is drained by the same iteration which reports the file descriptors that
+/// were registered while it was notified.
+#[test]
+fn interrupts_are_drained_alongside_ready_registrations() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let pipes = (0..3).map(|_| pipe()).collect::<Vec<_>>();
+
+    let ready = pipes
+        .iter()
+        .map(|(reader, writer)| {
+            write_byte(writer);
+            let ready = Arc::new(AtomicI16::new(0));
+            handle
+                .register(
+                    reader.as_raw_fd(),
+                    Interest::READ,
+                    ready.clone(),
+                    noop_waker(),
+                )
+                .unwrap();
+            ready
+        })
+        .collect::<Vec<_>>();
+    assert!(handle.shared.interrupt_pending.load(Ordering::SeqCst));
+
+    let report = reactor.step_once(Some(TIMEOUT)).unwrap();
+    assert_eq!((report.ready, report.matched), (pipes.len(), pipes.len()));
+    assert!(!handle.shared.interrupt_pending.load(Ordering::SeqCst));
+    for ready in ready {
+        assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+    }
+}
+
+/// A thousand file descriptors registered at once are all polled after the single interrupt
+/// which registering them notified, and are all unregistered at once.
+#[test]
+fn many_registrations_share_an_interrupt() {
+    const FDS: usize = 1000;
+
+    // Run in a child, so that the file descriptors which the other tests close are not
+    // reused by these.
+    if !in_child_process("reactor::tests::many_registrations_share_an_interrupt") {
+        return;
+    }
+
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let mut driver = reactor.take_driver().unwrap().unwrap();
+    let mut step = |timeout| match driver.step(&handle.shared, Some(timeout)) {
+        Step::Continue(report) => report,
+        _ => panic!("the reactor stopped"),
+    };
+
+    let pipes = (0..FDS).map(|_| pipe()).collect::<Vec<_>>();
+    let ready = (0..FDS)
+        .map(|_| Arc::new(AtomicI16::new(0)))
+        .collect::<Vec<_>>();
+    for (_, writer) in &pipes {
+        write_byte(writer);
+    }
+
+    let tokens = handle
+        .register_many(pipes.iter().zip(&ready).map(|((reader, _), ready)| {
+            (
+                reader.as_raw_fd(),
+                Interest::READ,
+                ready.clone(),
+                noop_waker(),
+            )
+        }))
+        .unwrap();
+
+    // Woken by the interrupt, after which the poller is updated with every registration,
+    // which the next wait reports without being interrupted again.
+    assert_eq!(step(TIMEOUT).ready, 0);
+    assert!(!handle.shared.interrupt_pending.load(Ordering::SeqCst));
+    assert_eq!(step(TIMEOUT).matched, FDS);
+    assert!(ready.iter().all(|ready| ready.load(Ordering::SeqCst) != 0));
+
+    let fds = pipes.iter().map(|(reader, _)| reader.as_raw_fd());
+    handle.unregister_many(fds.zip(tokens));
+    assert!(handle.registered().is_empty());
+    assert_eq!(step(TIMEOUT).matched, 0);
+    assert_eq!(step(Duration::from_millis(10)).ready, 0);
+}
+
+/// The snapshot of registered file descriptors reflects every registration and
+/// unregistration, with the combined interest of the registrations waiting on each.
+#[test]
+fn registered_file_descriptors_are_snapshotted() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let ((reader, _writer), (other, _other)) = (pipe(), pipe());
+    let (fd, other) = (reader.as_raw_fd(), other.as_raw_fd());
+
+    let register = |fd, interest| {
+        let ready = Arc::new(AtomicI16::new(0));
+        handle.register(fd, interest, ready, noop_waker()).unwrap()
+    };
+
+    let read = register(fd, Interest::READ);
+    let write = register(fd, Interest::WRITE);
+    let another = register(other, Interest::READ);
+
+    let mut registered = handle.registered();
+    registered.sort_unstable_by_key(|&(fd, _)| fd);
+    assert_eq!(registered, [(fd, Interest::BOTH), (other, Interest::READ)]);
+    assert!(handle.is_registered(fd) && handle.is_registered(other));
+
+    handle.unregister(fd, read);
+    assert_eq!(handle.registered().len(), 2);
+    assert!(handle.registered().contains(&(fd, Interest::WRITE)));
+
+    handle.unregister(fd, write);
+    assert_eq!(handle.registered(), [(other, Interest::READ)]);
+    assert!(!handle.is_registered(fd));
+
+    handle.unregister(other, another);
+    assert!(handle.registered().is_empty());
+    assert!(!handle.is_registered(other));
+}
+
+/// The reactor's thread takes its configured name, as shown by the kernel.
+#[cfg(target_os = "linux")]
+#[test]
+fn reactor_threads_are_named() {
+    let names = || {
+        std::fs::read_dir("/proc/self/task")
+            .unwrap()
+            .filter_map(|task| std::fs::read_to_string(task.ok()?.path().join("comm")).ok())
+            .map(|name| name.trim_end().to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    let (_reactor, handle) = Reactor::builder()
+        .thread_name("named-reactor")
+        .build()
+        .unwrap();
+    handle.sync();
+    assert!(names().iter().any(|name| name == "named-reactor"));
+}
+
+/// Level-triggered registrations are woken by every poll which finds their file descriptor
+/// ready, and remain registered.
+#[test]
+fn level_triggered_registrations_are_woken_while_ready() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+    write_byte(&writer);
+
+    let ready = Arc::new(AtomicI16::new(0));
+    handle
+        .register_with_mode(fd, Interest::READ, Mode::Level, ready.clone(), noop_waker())
+        .unwrap();
+
+    for _ in 0..3 {
+        assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+        assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+        assert!(handle.is_registered(fd));
+    }
+}
+
+/// Edge-triggered registrations are woken once by readiness, and only woken again by new
+/// events once their events were cleared.
+#[test]
+fn edge_triggered_registrations_are_woken_by_new_events() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+    write_byte(&writer);
+
+    let ready = Arc::new(AtomicI16::new(0));
+    handle
+        .register_with_mode(fd, Interest::READ, Mode::Edge, ready.clone(), noop_waker())
+        .unwrap();
+
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    let step = reactor.step_once(Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(step.woken, 0);
+    assert!(handle.is_registered(fd));
+
+    ready.store(0, Ordering::SeqCst);
+    write_byte(&writer);
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+}
+
+/// One-shot registrations are removed once their first events were delivered, however
+/// long their file descriptor remains ready.
+#[test]
+fn one_shot_registrations_are_woken_once() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+    write_byte(&writer);
+
+    let ready = Arc::new(AtomicI16::new(0));
+    handle
+        .register_with_mode(
+            fd,
+            Interest::READ,
+            Mode::OneShot,
+            ready.clone(),
+            noop_waker(),
+        )
+        .unwrap();
+
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+    assert!(!handle.is_registered(fd));
+
+    let step = reactor.step_once(Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(step, StepReport::default());
+}
+
+/// A plain thread, with no executor, blocks until its file descriptor is ready, or until
+/// its timeout elapses.
+#[test]
+fn plain_threads_wait_until_ready() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let why = handle
+        .wait_ready(fd, Interest::READ, Some(Duration::from_millis(10)))
+        .unwrap_err();
+    assert_eq!(why.kind(), io::ErrorKind::TimedOut);
+    assert!(!handle.is_registered(fd));
+
+    thread::scope(|scope| {
+        let waiting = scope.spawn(|| handle.wait_ready(fd, Interest::READ, None));
+        wait_until(|| handle.is_registered(fd));
+        write_byte(&writer);
+        assert_eq!(waiting.join().unwrap().unwrap(), Interest::READ);
+    });
+    assert!(!handle.is_registered(fd));
+}
+
+/// Negative file descriptors, and those of the reactor's own interrupt, are refused, after
+/// which the reactor keeps polling the file descriptors which are registered.
+#[test]
+fn invalid_file_descriptors_are_refused() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let interrupt = handle.shared.interrupt.as_raw_fd();
+
+    for fd in [-1, interrupt] {
+        let ready = Arc::new(AtomicI16::new(0));
+        let why = handle
+            .register(fd, Interest::READ, ready, noop_waker())
+            .unwrap_err();
+        assert_eq!(why.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    let registrations = [reader.as_raw_fd(), -1].map(|fd| {
+        let ready = Arc::new(AtomicI16::new(0));
+        (fd, Interest::READ, ready, noop_waker())
+    });
+    let why = handle.register_many(registrations).unwrap_err();
+    assert_eq!(why.kind(), io::ErrorKind::InvalidInput);
+    assert!(handle.registered().is_empty());
+
+    write_byte(&writer);
+    let events = handle.wait_ready(reader.as_raw_fd(), Interest::READ, Some(TIMEOUT));
+    assert_eq!(events.unwrap(), Interest::READ);
+    assert!(handle.last_error().is_none() && !handle.is_shutdown());
+}
+
+/// Each waker which the reactor wakes is counted, along with the polls which woke them, as
+/// is each registered file descriptor.
+#[test]
+fn wakeups_are_counted() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+    assert_eq!(handle.metrics().wakeups, 0);
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let mode = Mode::OneShot;
+    handle
+        .register_with_mode(fd, Interest::READ, mode, ready, noop_waker())
+        .unwrap();
+    assert_eq!(handle.metrics().registered_fds, 1);
+
+    // The thread which waits for the reactor to sync is itself woken by the reactor.
+    handle.sync();
+    let woken = handle.metrics().wakeups;
+    assert_eq!(woken, 1);
+
+    write_byte(&writer);
+    wait_until(|| handle.metrics().wakeups == woken + 1);
+    assert!(handle.metrics().poll_iterations >= 1);
+    assert_eq!(handle.metrics().registered_fds, 0);
+}
+
+/// A signal which is blocked on the reactor's thread is only delivered while it waits with
+/// its signal mask, and the handler's self-pipe then wakes the registration on it.
+#[cfg(target_os = "linux")]
+#[test]
+fn signals_are_unblocked_while_waiting() {
+    use std::{os::unix::thread::JoinHandleExt, ptr, sync::atomic::AtomicI32};
+
+    static SELF_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn handler(_: libc::c_int) {
+        let fd = SELF_PIPE.load(Ordering::SeqCst);
+        unsafe { libc::write(fd, b"0".as_ptr().cast(), 1) };
+    }
+
+    if !in_child_process("reactor::tests::signals_are_unblocked_while_waiting") {
+        return;
+    }
+
+    let (reader, writer) = pipe();
+    SELF_PIPE.store(writer.as_raw_fd(), Ordering::SeqCst);
+    let mut action: libc::sigaction = unsafe { mem::zeroed() };
+    action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    assert_eq!(
+        unsafe { libc::sigaction(libc::SIGUSR1, &action, ptr::null_mut()) },
+        0
+    );
+
+    // Blocked before the reactor's thread is spawned, which inherits the mask.
+    let mut blocked: libc::sigset_t = unsafe { mem::zeroed() };
+    let mut unblocked: libc::sigset_t = unsafe { mem::zeroed() };
+    unsafe {
+        libc::sigemptyset(&mut blocked);
+        libc::sigaddset(&mut blocked, libc::SIGUSR1);
+        libc::sigemptyset(&mut unblocked);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &blocked, ptr::null_mut());
+    }
+
+    let (_reactor, handle) = Reactor::builder().signal_mask(unblocked).build().unwrap();
+    let ready = Arc::new(AtomicI16::new(0));
+    handle
+        .register(
+            reader.as_raw_fd(),
+            Interest::READ,
+            ready.clone(),
+            noop_waker(),
+        )
+        .unwrap();
+    handle.sync();
+
+    let thread = handle
+        .shared
+        .registry()
+        .thread
+        .as_ref()
+        .unwrap()
+        .as_pthread_t();
+    assert_eq!(unsafe { libc::pthread_kill(thread, libc::SIGUSR1) }, 0);
+    wait_until(|| ready.load(Ordering::SeqCst) != 0);
+    assert!(handle.last_error().is_none() && !handle.is_shutdown());
+}
+
+/// However many notifications are pending on the interrupt, a single iteration drains them
+/// all, so that the interrupt does not remain readable and wake every later wait.
+#[test]
+fn pending_notifications_are_drained_at_once() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let interrupt = handle.shared.interrupt.as_raw_fd();
+
+    for _ in 0..1000 {
+        handle.shared.interrupt.notify().unwrap();
+    }
+    assert!(crate::sys::poll_now(interrupt, Interest::READ)
+        .unwrap()
+        .is_readable());
+
+    reactor.step_once(Some(TIMEOUT)).unwrap();
+    let events = crate::sys::poll_now(interrupt, Interest::READ).unwrap();
+    assert!(!events.is_readable());
+}
+
+/// Once the reactor holds its maximum number of file descriptors, registering another is
+/// refused, even one which is always ready, while more registrations of those which are
+/// registered are accepted, and the registered file descriptors keep being polled.
+#[test]
+fn registrations_beyond_the_maximum_are_refused() {
+    const MAX_FDS: usize = 3;
+
+    let (_reactor, handle) = Reactor::builder().max_fds(MAX_FDS).build().unwrap();
+    let register = |fd| {
+        let ready = Arc::new(AtomicI16::new(0));
+        handle
+            .register(fd, Interest::READ, ready.clone(), noop_waker())
+            .map(|token| (token, ready))
+    };
+
+    let pipes = (0..=MAX_FDS).map(|_| pipe()).collect::<Vec<_>>();
+    let registered = pipes[..MAX_FDS]
+        .iter()
+        .map(|(reader, _)| register(reader.as_raw_fd()).unwrap())
+        .collect::<Vec<_>>();
+
+    // A regular file, which is always ready rather than polled.
+    let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+    for fd in [pipes[MAX_FDS].0.as_raw_fd(), file.as_raw_fd()] {
+        let why = register(fd).unwrap_err();
+        assert!(why.to_string().contains("maximum number"), "{}", why);
+    }
+
+    let (token, _) = register(pipes[0].0.as_raw_fd()).unwrap();
+    handle.unregister(pipes[0].0.as_raw_fd(), token);
+    assert_eq!(handle.registered().len(), MAX_FDS);
+
+    write_byte(&pipes[1].1);
+    wait_until(|| registered[1].1.load(Ordering::SeqCst) != 0);
+
+    let (fd, (token, _)) = (pipes[2].0.as_raw_fd(), &registered[2]);
+    handle.unregister(fd, *token);
+    register(pipes[MAX_FDS].0.as_raw_fd()).unwrap();
+}
+
+/// Creating a reactor fails with the error of the file descriptor which could not be
+/// opened, once the process has none to spare.
+#[test]
+fn reactors_fail_to_start_without_file_descriptors() {
+    if !in_child_process("reactor::tests::reactors_fail_to_start_without_file_descriptors") {
+        return;
+    }
+
+    exhaust_open_files();
+    let why = Reactor::new().map(drop).unwrap_err();
+    assert_eq!(why.raw_os_error(), Some(libc::EMFILE));
+}
+
+/// Probing a file descriptor reports only the events which are ready at that moment, without
+/// registering it.
+#[test]
+fn probes_report_current_readiness() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let probe = |fd: &OwnedFd, interest| handle.poll_now(fd.as_raw_fd(), interest).unwrap();
+
+    assert_eq!(probe(&reader, Interest::READ), Interest::empty());
+    assert_eq!(probe(&writer, Interest::WRITE), Interest::WRITE);
+
+    // Filled until writing would block, which leaves the reader with data to read.
+    while unsafe { libc::write(writer.as_raw_fd(), [0u8; 4096].as_ptr().cast(), 4096) } > 0 {}
+    assert_eq!(probe(&reader, Interest::READ), Interest::READ);
+    assert_eq!(probe(&writer, Interest::WRITE), Interest::empty());
+    assert!(handle.registered().is_empty());
+}
+
+/// Callbacks are called on the reactor's thread with the events which were observed, and
+/// one-shot callbacks only once.
+#[test]
+fn callbacks_are_called_with_their_events() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let called = Arc::new(AtomicI16::new(0));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let callback = {
+        let (called, calls) = (called.clone(), calls.clone());
+        Box::new(move |events: Interest| {
+            called.fetch_or(events.bits(), Ordering::SeqCst);
+            calls.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+    handle
+        .register_callback_with_mode(fd, Interest::READ, Mode::OneShot, callback)
+        .unwrap();
+    handle.sync();
+    assert_eq!(called.load(Ordering::SeqCst), 0);
+
+    write_byte(&writer);
+    wait_until(|| called.load(Ordering::SeqCst) != 0);
+    assert_eq!(called.load(Ordering::SeqCst), Interest::READ.bits());
+
+    // The reactor would call it again had it remained registered, as the pipe is readable.
+    handle.sync();
+    assert!(!handle.is_registered(fd));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+/// Registrations which observe an error or hangup condition are removed, rather than having
+/// the reactor report the persisting condition on every poll.
+#[test]
+fn terminal_conditions_remove_their_registrations() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (hung_up, writer) = pipe();
+    let (reader, errored) = pipe();
+    drop((writer, reader));
+
+    let registrations = [(&hung_up, Interest::READ), (&errored, Interest::WRITE)];
+    let ready = registrations.map(|(fd, interest)| {
+        let ready = Arc::new(AtomicI16::new(0));
+        let fd = fd.as_raw_fd();
+        handle
+            .register(fd, interest, ready.clone(), noop_waker())
+            .unwrap();
+        ready
+    });
+
+    wait_until(|| handle.registered().is_empty());
+    let events = ready.map(|ready| Interest::from_bits_truncate(ready.load(Ordering::SeqCst)));
+    assert!(events[0].contains(Interest::HANGUP));
+    // Which kqueue reports as a hangup of the write filter.
+    assert!(events[1].intersects(Interest::ERROR | Interest::HANGUP));
+
+    // A reactor which kept polling them would spin through many iterations meanwhile.
+    let iterations = handle.metrics().poll_iterations;
+    thread::sleep(Duration::from_millis(50));
+    assert!(handle.metrics().poll_iterations - iterations <= 1);
+}
+
+/// The maintenance interval wakes the reactor's loop while nothing is ready, whereas the
+/// loop otherwise keeps waiting.
+#[test]
+fn maintenance_intervals_wake_the_loop() {
+    let iterations = |builder: ReactorBuilder| {
+        let (_reactor, handle) = builder.build().unwrap();
+        let (reader, _writer) = pipe();
+        let ready = Arc::new(AtomicI16::new(0));
+        handle
+            .register(reader.as_raw_fd(), Interest::READ, ready, noop_waker())
+            .unwrap();
+        handle.sync();
+
+        let iterations = handle.metrics().poll_iterations;
+        thread::sleep(Duration::from_millis(100));
+        handle.metrics().poll_iterations - iterations
+    };
+
+    let interval = Reactor::builder().maintenance_interval(Duration::from_millis(10));
+    assert!(iterations(interval) >= 3);
+    assert_eq!(iterations(Reactor::builder()), 0);
+}
+
+/// The debug output of a handle shows how many file descriptors are registered, and whether
+/// the reactor has shut down.
+#[test]
+fn debug_output_shows_registrations() {
+    let (reactor, handle) = Reactor::new().unwrap();
+    let ((first, _first), (second, _second)) = (pipe(), pipe());
+
+    for fd in [first.as_raw_fd(), second.as_raw_fd(), second.as_raw_fd()] {
+        let ready = Arc::new(AtomicI16::new(0));
+        handle
+            .register(fd, Interest::READ, ready, noop_waker())
+            .unwrap();
+    }
+
+    let debug = format!("{:?}", handle);
+    assert!(debug.contains("registered_fds: 2"), "{}", debug);
+    assert!(debug.contains("shutdown: false"), "{}", debug);
+
+    reactor.shutdown();
+    let debug = format!("{:?}", handle);
+    assert!(debug.contains("registered_fds: 0"), "{}", debug);
+    assert!(debug.contains("shutdown: true"), "{}", debug);
+}
+
+/// An owned registration polls its duplicate after the caller closed the original file
+/// descriptor, and is woken by the readiness of their shared file.
+#[test]
+fn owned_registrations_outlive_the_original() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let (duplicate, token) = handle
+        .register_owned(reader.as_fd(), Interest::READ, ready.clone(), noop_waker())
+        .unwrap();
+    assert_ne!(duplicate, reader.as_raw_fd());
+    drop(reader);
+    handle.sync();
+
+    write_byte(&writer);
+    wait_until(|| ready.load(Ordering::SeqCst) != 0);
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+    assert!(handle.is_registered(duplicate));
+
+    handle.unregister(duplicate, token);
+    assert!(!handle.is_registered(duplicate));
+}
+
+/// While several file descriptors remain ready, each is woken first by its share of the
+/// iterations, rather than the same one always being woken before the others.
+#[test]
+fn wakeups_are_balanced_across_ready_file_descriptors() {
+    const FDS: usize = 4;
+    const ITERATIONS: usize = 40;
+
+    struct Ordered(usize, Arc<Mutex<Vec<usize>>>);
+
+    impl Wake for Ordered {
+        fn wake(self: Arc<Self>) {
+            self.1.lock().unwrap().push(self.0);
+        }
+    }
+
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let pipes = (0..FDS).map(|_| pipe()).collect::<Vec<_>>();
+    let woken = Arc::new(Mutex::new(Vec::new()));
+
+    for (index, (reader, writer)) in pipes.iter().enumerate() {
+        write_byte(writer);
+        let waker = Waker::from(Arc::new(Ordered(index, woken.clone())));
+        let ready = Arc::new(AtomicI16::new(0));
+        handle
+            .register(reader.as_raw_fd(), Interest::READ, ready, waker)
+            .unwrap();
+    }
+
+    let mut first = [0; FDS];
+    for _ in 0..ITERATIONS {
+        assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, FDS);
+        first[woken.lock().unwrap()[0]] += 1;
+        woken.lock().unwrap().clear();
+    }
+
+    assert!(
+        first.iter().all(|&count| count >= ITERATIONS / FDS / 2),
+        "{:?}",
+        first
+    );
+}
+
+/// A guarded registration is unregistered once its guard is dropped, after which the
+/// reactor no longer polls its file descriptor.
+#[test]
+fn dropping_guards_unregisters() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let guard = handle
+        .register_guarded(fd, Interest::READ, ready.clone(), noop_waker())
+        .unwrap();
+    assert_eq!(guard.fd(), fd);
+    assert_eq!(handle.waiter_count(fd), 1);
+
+    drop(guard);
+    assert!(!handle.is_registered(fd));
+
+    handle.sync();
+    write_byte(&writer);
+    handle.sync();
+    assert_eq!(ready.load(Ordering::SeqCst), 0);
+}
+
+/// A custom spawner runs the reactor's loop, on a thread of its own configuration, and is
+/// called again to respawn the thread once it exited while idle.
+#[test]
+fn custom_spawners_run_the_loop() {
+    let spawned = Arc::new(AtomicUsize::new(0));
+    let spawner = {
+        let spawned = spawned.clone();
+        move |run: Box<dyn FnOnce() + Send>| {
+            spawned.fetch_add(1, Ordering::SeqCst);
+            thread::Builder::new()
+                .name("spawned-reactor".to_owned())
+                .stack_size(256 * 1024)
+                .spawn(run)
+        }
+    };
+
+    let (_reactor, handle) = Reactor::builder()
+        .spawner(spawner)
+        .idle_timeout(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    assert_eq!(spawned.load(Ordering::SeqCst), 1);
+
+    let (reader, writer) = pipe();
+    write_byte(&writer);
+    let events = handle.wait_ready(reader.as_raw_fd(), Interest::READ, Some(TIMEOUT));
+    assert_eq!(events.unwrap(), Interest::READ);
+
+    // Respawned by the next registration once the thread exited while idle.
+    wait_until(|| handle.shared.registry().driver.is_some());
+    let events = handle.wait_ready(reader.as_raw_fd(), Interest::READ, Some(TIMEOUT));
+    assert_eq!(events.unwrap(), Interest::READ);
+    assert_eq!(spawned.load(Ordering::SeqCst), 2);
+}
+
+/// The next deadline is the earliest of those of the registrations which remain.
+#[test]
+fn next_deadlines_are_the_earliest() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let ((first, _first), (second, _second)) = (pipe(), pipe());
+    assert_eq!(handle.next_deadline(), None);
+
+    let now = Instant::now();
+    let (sooner, later) = (now + TIMEOUT, now + TIMEOUT * 2);
+    let register = |fd: &OwnedFd, deadline| {
+        let ready = Arc::new(AtomicI16::new(0));
+        handle
+            .register_with_deadline(
+                fd.as_raw_fd(),
+                Interest::READ,
+                ready,
+                noop_waker(),
+                deadline,
+            )
+            .unwrap()
+    };
+
+    let later_token = register(&first, later);
+    assert_eq!(handle.next_deadline(), Some(later));
+    let sooner_token = register(&second, sooner);
+    assert_eq!(handle.next_deadline(), Some(sooner));
+
+    handle.unregister(second.as_raw_fd(), sooner_token);
+    assert_eq!(handle.next_deadline(), Some(later));
+    handle.unregister(first.as_raw_fd(), later_token);
+    assert_eq!(handle.next_deadline(), None);
+}
+
+/// Forced events wake the registrations of a file descriptor which is never ready, as the
+/// reactor would, including once they were moved onto another reactor.
+#[test]
+fn forced_events_wake_registrations() {
+    let (drained, from) = Reactor::new().unwrap();
+    let (_reactor, into) = Reactor::new().unwrap();
+    let (reader, _writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let register = |mode| {
+        let ready = Arc::new(AtomicI16::new(0));
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        from.register_with_mode(fd, Interest::READ, mode, ready.clone(), waker)
+            .unwrap();
+        (ready, counter)
+    };
+
+    // The write interest is not among those of the registration, so it is not woken by it.
+    let (level, level_woken) = register(Mode::Level);
+    from.force_ready(fd, Interest::WRITE);
+    assert_eq!(level_woken.0.load(Ordering::SeqCst), 0);
+
+    from.force_ready(fd, Interest::READ);
+    assert_eq!(level.load(Ordering::SeqCst), Interest::READ.bits());
+    assert_eq!(level_woken.0.load(Ordering::SeqCst), 1);
+    assert!(from.is_registered(fd));
+
+    let (one_shot, one_shot_woken) = register(Mode::OneShot);
+    drained.drain_into(&into).unwrap();
+    from.force_ready(fd, Interest::READ);
+    assert_eq!(one_shot.load(Ordering::SeqCst), Interest::READ.bits());
+    assert_eq!(one_shot_woken.0.load(Ordering::SeqCst), 1);
+    assert_eq!(into.waiter_count(fd), 1);
+}
+
+/// Registrations of the same file descriptor are each counted, and polled for the union of
+/// their interests.
+#[test]
+fn effective_interests_combine_every_registration() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (socket, _peer) = socketpair();
+    let fd = socket.as_raw_fd();
+    assert_eq!(
+        (handle.effective_interest(fd), handle.waiter_count(fd)),
+        (None, 0)
+    );
+
+    let tokens = [Interest::READ, Interest::WRITE].map(|interest| {
+        let ready = Arc::new(AtomicI16::new(0));
+        let (mode, waker) = (Mode::Edge, noop_waker());
+        handle
+            .register_with_mode(fd, interest, mode, ready, waker)
+            .unwrap()
+    });
+    assert_eq!(handle.effective_interest(fd), Some(Interest::BOTH));
+    assert_eq!(handle.waiter_count(fd), 2);
+
+    handle.unregister(fd, tokens[1]);
+    assert_eq!(handle.effective_interest(fd), Some(Interest::READ));
+    assert_eq!(handle.waiter_count(fd), 1);
+    handle.unregister(fd, tokens[0]);
+    assert_eq!(handle.effective_interest(fd), None);
+}
+
+/// A suspended registration is not woken by data which arrives meanwhile, and is woken by
+/// it once its interest is restored.
+#[test]
+fn suspended_registrations_wake_once_resumed() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let counter = Arc::new(CountingWaker::default());
+    let waker = Waker::from(counter.clone());
+    let token = handle
+        .register(fd, Interest::READ, ready.clone(), waker)
+        .unwrap();
+
+    handle.modify_interest(fd, token, Interest::empty());
+    handle.sync();
+    write_byte(&writer);
+    thread::sleep(Duration::from_millis(20));
+    handle.sync();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+    assert_eq!(handle.effective_interest(fd), Some(Interest::empty()));
+
+    handle.modify_interest(fd, token, Interest::READ);
+    wait_until(|| counter.0.load(Ordering::SeqCst) != 0);
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+    handle.unregister(fd, token);
+}
+
+/// The time a file descriptor was last ready only moves when the reactor finds it ready
+/// again, not whenever the loop wakes.
+#[test]
+fn last_ready_is_kept_without_new_events() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let token = handle
+        .register(fd, Interest::READ, ready, noop_waker())
+        .unwrap();
+    assert_eq!(handle.last_ready(fd), None);
+
+    write_byte(&writer);
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    let last = handle.last_ready(fd).unwrap();
+    read_byte(&reader).unwrap();
+
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(
+        reactor
+            .step_once(Some(Duration::from_millis(10)))
+            .unwrap()
+            .woken,
+        0
+    );
+    assert_eq!(handle.last_ready(fd), Some(last));
+
+    write_byte(&writer);
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    assert!(handle.last_ready(fd).unwrap() > last);
+    handle.unregister(fd, token);
+}
+
+/// A file descriptor which exhausts its wakeup budget is deferred for the rest of the
+/// window, while one which is only occasionally ready is still woken.
+#[test]
+fn hot_file_descriptors_do_not_starve_occasional_ones() {
+    let (reactor, handle) = Reactor::builder()
+        .current_thread()
+        .wakeup_budget(2, TIMEOUT)
+        .build()
+        .unwrap();
+    let (hot, hot_writer) = pipe();
+    let (occasional, occasional_writer) = pipe();
+    write_byte(&hot_writer);
+
+    let hot_wakeups = Arc::new(CountingWaker::default());
+    let occasional_wakeups = Arc::new(CountingWaker::default());
+    let hot_token = handle
+        .register(
+            hot.as_raw_fd(),
+            Interest::READ,
+            Arc::new(AtomicI16::new(0)),
+            Waker::from(hot_wakeups.clone()),
+        )
+        .unwrap();
+    let occasional_token = handle
+        .register(
+            occasional.as_raw_fd(),
+            Interest::READ,
+            Arc::new(AtomicI16::new(0)),
+            Waker::from(occasional_wakeups.clone()),
+        )
+        .unwrap();
+
+    for _ in 0..2 {
+        assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    }
+    let step = reactor.step_once(Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(step.woken, 0);
+    assert_eq!(hot_wakeups.0.load(Ordering::SeqCst), 2);
+
+    write_byte(&occasional_writer);
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    assert_eq!(occasional_wakeups.0.load(Ordering::SeqCst), 1);
+    assert_eq!(hot_wakeups.0.load(Ordering::SeqCst), 2);
+
+    handle.unregister(hot.as_raw_fd(), hot_token);
+    handle.unregister(occasional.as_raw_fd(), occasional_token);
+}
+
+/// Clones of a handle register with the same reactor, which keeps running while any of
+/// them is alive.
+#[test]
+fn cloned_handles_register_with_the_same_reactor() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let clone = handle.clone();
+    drop(handle);
+
+    let counter = Arc::new(CountingWaker::default());
+    let waker = Waker::from(counter.clone());
+    let (clone, token) = thread::spawn(move || {
+        let token = clone
+            .register(fd, Interest::READ, Arc::new(AtomicI16::new(0)), waker)
+            .unwrap();
+        (clone, token)
+    })
+    .join()
+    .unwrap();
+    assert!(clone.is_registered(fd));
+
+    write_byte(&writer);
+    wait_until(|| counter.0.load(Ordering::SeqCst) != 0);
+    clone.unregister(fd, token);
+}
+
+/// Iterations whose wakers take longer than the slow threshold are warned of to the
+/// subscriber of the thread which runs the loop.
+#[cfg(feature = "tracing")]
+#[test]
+fn slow_iterations_are_warned_of() {
+    use std::sync::Mutex;
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Level, Metadata, Subscriber,
+    };
+
+    struct Slow;
+
+    impl Wake for Slow {
+        fn wake(self: Arc<Self>) {
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Warnings(Arc<Mutex<Vec<String>>>);
+
+    struct Message<'a>(&'a mut String);
+
+    impl Visit for Message<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                *self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    impl Subscriber for Warnings {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            if *event.metadata().level() == Level::WARN {
+                let mut message = String::new();
+                event.record(&mut Message(&mut message));
+                self.0.lock().unwrap().push(message);
+            }
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    let (reactor, handle) = Reactor::builder()
+        .current_thread()
+        .slow_threshold(Duration::from_millis(5))
+        .build()
+        .unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let token = handle
+        .register(
+            fd,
+            Interest::READ,
+            Arc::new(AtomicI16::new(0)),
+            Waker::from(Arc::new(Slow)),
+        )
+        .unwrap();
+
+    let warnings = Warnings::default();
+    tracing::subscriber::with_default(warnings.clone(), || {
+        let step = reactor.step_once(Some(Duration::from_millis(10))).unwrap();
+        assert_eq!(step.woken, 0);
+        assert!(warnings.0.lock().unwrap().is_empty());
+
+        write_byte(&writer);
+        assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    });
+
+    let warnings = warnings.0.lock().unwrap();
+    assert_eq!(*warnings, ["fd-reactor was slow to wake its registrations"]);
+    drop(warnings);
+    handle.unregister(fd, token);
+}
+
+/// One-shot callbacks are removed after their first call, while level-triggered callbacks
+/// of the same file descriptor are called on every poll which finds it ready.
+#[test]
+fn one_shot_callbacks_are_removed_once_called() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+    write_byte(&writer);
+
+    let counting = |calls: &Arc<AtomicUsize>| {
+        let calls = calls.clone();
+        Box::new(move |_: Interest| {
+            calls.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    let (once, level) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    handle
+        .register_callback_with_mode(fd, Interest::READ, Mode::OneShot, counting(&once))
+        .unwrap();
+    let token = handle
+        .register_callback(fd, Interest::READ, counting(&level))
+        .unwrap();
+    assert_eq!(handle.waiter_count(fd), 2);
+
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 2);
+    assert_eq!(handle.waiter_count(fd), 1);
+
+    for _ in 0..2 {
+        assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    }
+    assert_eq!(once.load(Ordering::SeqCst), 1);
+    assert_eq!(level.load(Ordering::SeqCst), 3);
+    handle.unregister(fd, token);
+}
+
+/// A task waiting on several file descriptors which become ready together is woken once,
+/// rather than once for each of them.
+#[test]
+fn shared_wakers_are_woken_once_per_iteration() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let pipes = [pipe(), pipe(), pipe()];
+
+    let counter = Arc::new(CountingWaker::default());
+    let waker = Waker::from(counter.clone());
+    for (reader, writer) in &pipes {
+        write_byte(writer);
+        handle
+            .register_with_mode(
+                reader.as_raw_fd(),
+                Interest::READ,
+                Mode::OneShot,
+                Arc::new(AtomicI16::new(0)),
+                waker.clone(),
+            )
+            .unwrap();
+    }
+
+    let step = reactor.step_once(Some(TIMEOUT)).unwrap();
+    assert_eq!(step.matched, 3);
+    assert_eq!(step.woken, 1);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert!(pipes
+        .iter()
+        .all(|(reader, _)| !handle.is_registered(reader.as_raw_fd())));
+}
+
+/// Reactors built for the current thread deliver events while their loop runs on it, and
+/// return once their last handle is dropped.
+#[test]
+fn current_thread_loops_deliver_events() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let counter = Arc::new(CountingWaker::default());
+    handle
+        .register_with_mode(
+            fd,
+            Interest::READ,
+            Mode::OneShot,
+            ready.clone(),
+            Waker::from(counter.clone()),
+        )
+        .unwrap();
+
+    let running = thread::spawn(move || reactor.run_current_thread());
+    write_byte(&writer);
+    wait_until(|| counter.0.load(Ordering::SeqCst) != 0);
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+    assert!(!handle.is_registered(fd));
+
+    drop(handle);
+    within(move || running.join().unwrap().unwrap());
+}
+
+/// Reactors which require non-blocking file descriptors refuse those in blocking mode,
+/// which other reactors accept.
+#[test]
+fn blocking_file_descriptors_are_refused_when_required() {
+    let (_reactor, handle) = Reactor::builder().require_nonblocking().build().unwrap();
+    let (_permissive, permissive) = Reactor::new().unwrap();
+    let ((nonblocking, _writer), (blocking, _other)) = (pipe(), pipe());
+    let blocking = blocking.as_raw_fd();
+    assert_eq!(unsafe { libc::fcntl(blocking, libc::F_SETFL, 0) }, 0);
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let why = handle
+        .register(blocking, Interest::READ, ready.clone(), noop_waker())
+        .unwrap_err();
+    assert_eq!(why.kind(), io::ErrorKind::InvalidInput);
+    assert!(!handle.is_registered(blocking));
+
+    let fd = nonblocking.as_raw_fd();
+    let token = handle
+        .register(fd, Interest::READ, ready.clone(), noop_waker())
+        .unwrap();
+    handle.unregister(fd, token);
+
+    let token = permissive
+        .register(blocking, Interest::READ, ready, noop_waker())
+        .unwrap();
+    permissive.unregister(blocking, token);
+}
+
+/// Replacing the waker of a registration leaves its file descriptor polled throughout, so
+/// that readiness after the replacement wakes only the new waker.
+#[test]
+fn updated_wakers_replace_the_previous_in_place() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let (reader, writer) = pipe();
+    let fd = reader.as_raw_fd();
+
+    let (previous, next) = (
+        Arc::new(CountingWaker::default()),
+        Arc::new(CountingWaker::default()),
+    );
+    let ready = Arc::new(AtomicI16::new(0));
+    let token = handle
+        .register(fd, Interest::READ, ready, Waker::from(previous.clone()))
+        .unwrap();
+    let step = reactor.step_once(Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(step.woken, 0);
+
+    assert!(handle.update_waker(fd, token, Waker::from(next.clone())));
+    assert!(handle.shared.registry().changed.is_empty());
+    assert_eq!(handle.registered(), [(fd, Interest::READ)]);
+
+    write_byte(&writer);
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    assert_eq!(previous.0.load(Ordering::SeqCst), 0);
+    assert_eq!(next.0.load(Ordering::SeqCst), 1);
+
+    handle.unregister(fd, token);
+    assert!(!handle.update_waker(fd, token, noop_waker()));
+}
+
+/// Regular files are signaled the read and write events of their interest once, when they
+/// are registered, rather than being polled and woken on every iteration.
+#[test]
+fn regular_files_resolve_once() {
+    let (_reactor, handle) = Reactor::new().unwrap();
+    let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+    let fd = file.as_raw_fd();
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let counter = Arc::new(CountingWaker::default());
+    let waker = Waker::from(counter.clone());
+    let token = handle
+        .register(
+            fd,
+            Interest::READ | Interest::PRIORITY,
+            ready.clone(),
+            waker,
+        )
+        .unwrap();
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert!(!handle.is_registered(fd));
+
+    thread::sleep(Duration::from_millis(20));
+    handle.sync();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+    // Unregistering the token, which refers to no registration, has no effect.
+    handle.unregister(fd, token);
+    assert!(handle.registered().is_empty());
+}
+
+/// The report of an iteration counts every ready file descriptor, and separately those
+/// whose events a registration was interested in.
+#[test]
+fn steps_report_matched_and_unmatched_events() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let ((matched, matched_writer), (unmatched, unmatched_writer)) = (pipe(), pipe());
+    write_byte(&matched_writer);
+    write_byte(&unmatched_writer);
+
+    let tokens = [&matched, &unmatched].map(|reader| {
+        let ready = Arc::new(AtomicI16::new(0));
+        handle
+            .register(reader.as_raw_fd(), Interest::READ, ready, noop_waker())
+            .unwrap()
+    });
+
+    // Changed once the poller was updated, so that the poller still waits for reads.
+    let mut driver = reactor.take_driver().unwrap().unwrap();
+    driver.poller.update(&mut handle.shared.registry());
+    handle.modify_interest(unmatched.as_raw_fd(), tokens[1], Interest::WRITE);
+
+    match driver.step(&handle.shared, Some(TIMEOUT)) {
+        Step::Continue(report) => assert_eq!(
+            report,
+            StepReport {
+                ready: 2,
+                matched: 1,
+                woken: 1,
+            }
+        ),
+        _ => panic!("the reactor stopped"),
+    }
+    handle.shared.registry().driver = Some(driver);
+
+    // Once the poller is updated, the file descriptor is no longer polled for reads.
+    let report = reactor.step_once(Some(TIMEOUT)).unwrap();
+    assert_eq!((report.ready, report.matched), (1, 1));
+
+    handle.unregister(matched.as_raw_fd(), tokens[0]);
+    handle.unregister(unmatched.as_raw_fd(), tokens[1]);
+}
+
+/// Modifying the interest of a registration discards events which were signaled for the
+/// previous interest, so that what is signaled afterwards is only of the new interest.
+#[test]
+fn modified_interests_discard_stale_events() {
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let (socket, peer) = socketpair();
+    let fd = socket.as_raw_fd();
+    write_byte(&peer);
+
+    let ready = Arc::new(AtomicI16::new(0));
+    let token = handle
+        .register(fd, Interest::READ, ready.clone(), noop_waker())
+        .unwrap();
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::READ.bits());
+
+    handle.modify_interest(fd, token, Interest::WRITE);
+    assert_eq!(ready.load(Ordering::SeqCst), 0);
+
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 1);
+    assert_eq!(ready.load(Ordering::SeqCst), Interest::WRITE.bits());
+    handle.unregister(fd, token);
+}
+
+/// Registrations which become ready together are woken in order of their priority, however
+/// they were registered.
+#[test]
+fn higher_priorities_are_woken_first() {
+    struct Recorder {
+        priority: u8,
+        woken: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Wake for Recorder {
+        fn wake(self: Arc<Self>) {
+            self.woken.lock().unwrap().push(self.priority);
+        }
+    }
+
+    let (reactor, handle) = Reactor::builder().current_thread().build().unwrap();
+    let pipes = [pipe(), pipe(), pipe()];
+    let woken = Arc::new(Mutex::new(Vec::new()));
+
+    let mut tokens = Vec::new();
+    for ((reader, writer), priority) in pipes.iter().zip([0, 9, 5]) {
+        write_byte(writer);
+        let recorder = Recorder {
+            priority,
+            woken: woken.clone(),
+        };
+        let token = handle
+            .register_with_priority(
+                reader.as_raw_fd(),
+                Interest::READ,
+                priority,
+                Arc::new(AtomicI16::new(0)),
+                Waker::from(Arc::new(recorder)),
+            )
+            .unwrap();
+        tokens.push((reader.as_raw_fd(), token));
+    }
+
+    assert_eq!(reactor.step_once(Some(TIMEOUT)).unwrap().woken, 3);
+    assert_eq!(*woken.lock().unwrap(), [9, 5, 0]);
+
+    for (fd, token) in tokens {
+        handle.unregister(fd, token);
+    }
+}
@@ -0,0 +1,145 @@
+//! A `select(2)` style wrapper over a single `poll(2)` call, for code which is ported from
+//! read, write, and exception sets.
+
+use crate::Interest;
+use std::{collections::HashMap, io, os::unix::io::RawFd, time::Duration};
+
+/// The file descriptors of each set which were ready when [`wait_sets`] returned.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReadySets {
+    /// File descriptors which can be read from without blocking, or which reached the end
+    /// of their input, or have an error pending.
+    pub read: Vec<RawFd>,
+
+    /// File descriptors which can be written to without blocking, or have an error pending.
+    pub write: Vec<RawFd>,
+
+    /// File descriptors which have an exceptional condition, such as out-of-band data.
+    pub except: Vec<RawFd>,
+}
+
+impl ReadySets {
+    /// Whether no file descriptor of any set was ready.
+    pub fn is_empty(&self) -> bool {
+        self.read.is_empty() && self.write.is_empty() && self.except.is_empty()
+    }
+}
+
+/// Waits until any of the file descriptors of the sets is ready, or the timeout elapses,
+/// returning the ready file descriptors of each set.
+///
+/// This waits on the calling thread rather than on the reactor. Like `select`, a file
+/// descriptor may be given in several sets, readiness in the read and write sets includes
+/// hangups and errors, the exception set listens for [`Interest::PRIORITY`], and a timeout
+/// of `None` waits indefinitely. Unlike `select`, file descriptors are not limited by
+/// `FD_SETSIZE`, and one which is not open fails with [`io::ErrorKind::InvalidInput`], in
+/// place of `EBADF`. A signal which interrupts the wait fails with
+/// [`io::ErrorKind::Interrupted`].
+///
+/// ```
+/// use fd_reactor::wait_sets;
+/// use std::time::Duration;
+///
+/// let mut fds = [0; 2];
+/// unsafe { libc::pipe(fds.as_mut_ptr()) };
+///
+/// let ready = wait_sets(&[fds[0]], &[fds[1]], &[], Some(Duration::ZERO)).unwrap();
+/// assert!(ready.read.is_empty());
+/// assert_eq!(ready.write, [fds[1]]);
+/// ```
+pub fn wait_sets(
+    read: &[RawFd],
+    write: &[RawFd],
+    except: &[RawFd],
+    timeout: Option<Duration>,
+) -> io::Result<ReadySets> {
+    let mut pollfds: Vec<libc::pollfd> =
+        Vec::with_capacity(read.len() + write.len() + except.len());
+
+    // The index of each file descriptor within `pollfds`, and the index of the `pollfd` of
+    // each entry of the sets, by which their results are mapped back.
+    let mut positions: HashMap<RawFd, usize> = HashMap::with_capacity(pollfds.capacity());
+    let mut indices: [Vec<usize>; 3] = Default::default();
+
+    let sets = [
+        (read, Interest::READ),
+        (write, Interest::WRITE),
+        (except, Interest::PRIORITY),
+    ];
+
+    for ((fds, interest), indices) in sets.iter().zip(&mut indices) {
+        indices.reserve(fds.len());
+
+        for &fd in fds.iter() {
+            let index = *positions.entry(fd).or_insert_with(|| {
+                pollfds.push(libc::pollfd {
+                    fd,
+                    events: 0,
+                    revents: 0,
+                });
+                pollfds.len() - 1
+            });
+
+            pollfds[index].events |= interest.to_events();
+            indices.push(index);
+        }
+    }
+
+    if crate::sys::poll_fds(&mut pollfds, timeout)? == 0 {
+        return Ok(ReadySets::default());
+    }
+
+    let invalid = pollfds
+        .iter()
+        .find(|pollfd| Interest::from_revents(pollfd.revents).contains(Interest::INVALID));
+
+    if let Some(pollfd) = invalid {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("file descriptor {} is not open", pollfd.fd),
+        ));
+    }
+
+    let select = |indices: &[usize], ready: Interest| {
+        indices
+            .iter()
+            .map(|&index| &pollfds[index])
+            .filter(|pollfd| Interest::from_revents(pollfd.revents).intersects(ready))
+            .map(|pollfd| pollfd.fd)
+            .collect::<Vec<_>>()
+    };
+
+    Ok(ReadySets {
+        read: select(&indices[0], Interest::READ | Interest::TERMINAL),
+        write: select(&indices[1], Interest::WRITE | Interest::TERMINAL),
+        except: select(&indices[2], Interest::PRIORITY),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use std::os::unix::io::AsRawFd;
+
+    /// Each entry of each set is reported by the readiness of its own file descriptor,
+    /// however often that file descriptor is given across the sets.
+    #[test]
+    fn file_descriptors_of_several_sets_are_reported_in_each() {
+        let ((reader, writer), (idle, _idle)) = (pipe(), pipe());
+        write_byte(&writer);
+        let (reader, writer, idle) = (reader.as_raw_fd(), writer.as_raw_fd(), idle.as_raw_fd());
+
+        let ready = wait_sets(
+            &[idle, reader, reader],
+            &[writer, reader, idle],
+            &[reader, writer],
+            Some(TIMEOUT),
+        )
+        .unwrap();
+
+        assert_eq!(ready.read, [reader, reader]);
+        assert_eq!(ready.write, [writer]);
+        assert!(ready.except.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{FdFuture, Interest};
+use std::{
+    io, mem,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+};
+
+/// Receives signals through a `signalfd(2)`, waiting on the global reactor for them to
+/// arrive.
+///
+/// The signals of the mask must be blocked with `pthread_sigmask` in every thread,
+/// including the reactor's, or they will be delivered to a thread instead of being queued
+/// for the signalfd. Threads inherit the mask of the thread which spawns them, so signals
+/// are best blocked at the start of `main`, before the reactor is first used.
+pub struct SignalFd {
+    fd: OwnedFd,
+}
+
+impl SignalFd {
+    /// Creates a signalfd which receives the signals of the given mask.
+    pub fn new(mask: &libc::sigset_t) -> io::Result<Self> {
+        let fd = unsafe { libc::signalfd(-1, mask, libc::SFD_CLOEXEC | libc::SFD_NONBLOCK) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Waits for the next signal to arrive, returning the information which the kernel
+    /// recorded about it.
+    pub async fn recv(&self) -> io::Result<libc::signalfd_siginfo> {
+        loop {
+            match self.try_recv() {
+                Err(why) if why.kind() == io::ErrorKind::WouldBlock => {
+                    FdFuture::from_fd(&self.fd, Interest::READ)
+                        .try_ready()
+                        .await?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Receives a signal which has already arrived, failing with `WouldBlock` otherwise.
+    pub fn try_recv(&self) -> io::Result<libc::signalfd_siginfo> {
+        let mut info: libc::signalfd_siginfo = unsafe { mem::zeroed() };
+        let size = mem::size_of::<libc::signalfd_siginfo>();
+
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    &mut info as *mut libc::signalfd_siginfo as *mut libc::c_void,
+                    size,
+                )
+            };
+
+            if read == -1 {
+                let why = io::Error::last_os_error();
+                if why.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                return Err(why);
+            }
+
+            // Reads from a signalfd always return whole structures.
+            debug_assert_eq!(read as usize, size);
+            return Ok(info);
+        }
+    }
+}
+
+impl AsFd for SignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for SignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use std::{future::Future, pin::pin, task::Context};
+
+    /// A signal which is sent to the process, while every thread blocks it, is received
+    /// through the signalfd once the reactor finds it readable.
+    #[test]
+    fn signals_are_received() {
+        let test = "signal_fd::tests::signals_are_received";
+        if !in_child_process_blocking(test, &[libc::SIGUSR1]) {
+            return;
+        }
+
+        let mut mask: libc::sigset_t = unsafe { mem::zeroed() };
+        unsafe {
+            libc::sigemptyset(&mut mask);
+            libc::sigaddset(&mut mask, libc::SIGUSR1);
+        }
+
+        let signals = SignalFd::new(&mask).unwrap();
+        let mut received = pin!(signals.recv());
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(received.as_mut().poll(&mut cx).is_pending());
+
+        assert_eq!(unsafe { libc::kill(libc::getpid(), libc::SIGUSR1) }, 0);
+        let info = block_on(received).unwrap();
+        assert_eq!(info.ssi_signo, libc::SIGUSR1 as u32);
+        assert_eq!(info.ssi_pid, std::process::id());
+        assert_eq!(
+            signals.try_recv().unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{future::events_result, Handle, Interest, ReadyEvents, Token, REACTOR};
+use std::{
+    io,
+    os::unix::io::RawFd,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicI16, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// A stream which yields the events observed on a file descriptor each time that it is
+/// found ready.
+///
+/// Unlike an [`FdFuture`](crate::FdFuture), the file descriptor stays registered between
+/// items, and is only unregistered once the stream is dropped. Registrations are
+/// level-triggered, so a file descriptor which is not drained until it would block is
+/// yielded again by the next poll, and a file descriptor with an error or hangup condition
+/// is yielded again each time that the stream is polled.
+///
+/// Error conditions are yielded as errors, as with
+/// [`FdFuture::try_ready`](crate::FdFuture::try_ready). The stream ends after yielding the
+/// error of a file descriptor which the reactor is no longer able to poll.
+pub struct ReadinessStream<'a> {
+    /// The reactor which the file descriptor is registered onto.
+    handle: &'a Handle,
+
+    /// The file descriptor being watched.
+    fd: RawFd,
+
+    /// The events which the reactor should listen for.
+    interest: Interest,
+
+    /// Set by the reactor to the observed events, and cleared as each item is yielded.
+    ready: Arc<AtomicI16>,
+
+    /// The registration and waker that the reactor currently holds for this stream.
+    registration: Option<(Token, Waker)>,
+
+    /// Whether the stream has ended.
+    done: bool,
+}
+
+impl ReadinessStream<'static> {
+    /// Creates a stream which waits for the given interest on the file descriptor.
+    pub fn new(fd: RawFd, interest: Interest) -> Self {
+        Self::new_with(&REACTOR, fd, interest)
+    }
+}
+
+impl<'a> ReadinessStream<'a> {
+    /// Creates a stream which waits on the reactor of the given handle.
+    pub fn new_with(handle: &'a Handle, fd: RawFd, interest: Interest) -> Self {
+        Self {
+            handle,
+            fd,
+            interest,
+            ready: Arc::default(),
+            registration: None,
+            done: false,
+        }
+    }
+
+    /// Polls for the next events observed on the file descriptor.
+    ///
+    /// This is the stream's implementation of `Stream::poll_next`, which is implemented
+    /// with the `stream` feature.
+    pub fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<io::Result<ReadyEvents>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        let events = self.ready.swap(0, Ordering::SeqCst);
+        if events != 0 {
+            let events = ReadyEvents::from_bits_truncate(events);
+
+            // The reactor has already removed the registration of a closed file descriptor,
+            // and of one with an error or hangup condition, which registers again when the
+            // stream is next polled.
+            if events.intersects(Interest::INVALID | Interest::HANGUP | Interest::ERROR) {
+                self.registration = None;
+                self.done = events.contains(Interest::INVALID);
+            }
+
+            return Poll::Ready(Some(events_result(self.handle, self.fd, events)));
+        }
+
+        // Only register again if the reactor does not already hold a waker for this task.
+        let registered = self
+            .registration
+            .as_ref()
+            .is_some_and(|(_, waker)| waker.will_wake(cx.waker()));
+
+        if !registered {
+            self.unregister();
+
+            let (fd, interest, ready, waker) = (
+                self.fd,
+                self.interest,
+                self.ready.clone(),
+                cx.waker().clone(),
+            );
+
+            match self.handle.register(fd, interest, ready, waker.clone()) {
+                Ok(token) => self.registration = Some((token, waker)),
+                Err(why) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(why)));
+                }
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Removes the file descriptor from the reactor, if it is currently registered.
+    fn unregister(&mut self) {
+        if let Some((token, _)) = self.registration.take() {
+            self.handle.unregister(self.fd, token);
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<'a> futures_core::Stream for ReadinessStream<'a> {
+    type Item = io::Result<ReadyEvents>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        ReadinessStream::poll_next(self, cx)
+    }
+}
+
+impl<'a> Drop for ReadinessStream<'a> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, Reactor};
+    use std::{future::poll_fn, os::unix::io::AsRawFd};
+
+    /// Each time the file descriptor becomes ready, the stream yields its events, while it
+    /// stays registered between items until the stream is dropped.
+    #[test]
+    fn streams_yield_each_readiness() {
+        let (_reactor, handle) = Reactor::new().unwrap();
+        let (reader, writer) = pipe();
+        let fd = reader.as_raw_fd();
+
+        let mut stream = Box::pin(ReadinessStream::new_with(&handle, fd, Interest::READ));
+
+        for _ in 0..3 {
+            write_byte(&writer);
+            let events = block_on(poll_fn(|cx| stream.as_mut().poll_next(cx)));
+            assert_eq!(events.unwrap().unwrap(), Interest::READ);
+            read_byte(&reader).unwrap();
+            assert_eq!(handle.waiter_count(fd), 1);
+        }
+
+        drop(stream);
+        assert!(!handle.is_registered(fd));
+    }
+}
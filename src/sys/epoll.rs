@@ -0,0 +1,269 @@
+use super::SignalMask;
+use crate::{
+    reactor::{edge_triggered, merged_interest, Registry},
+    Interest,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    time::{Duration, Instant},
+};
+
+/// The maximum number of events which are received from a single call to `epoll_wait(2)`.
+const EVENTS_CAPACITY: usize = 1024;
+
+/// How often the registered file descriptors are checked for having been closed.
+///
+/// Unlike `poll(2)`, which reports a closed file descriptor as invalid, epoll silently stops
+/// polling it once its file is closed, and would leave its registrations waiting forever.
+/// A file descriptor whose number was reused by another file cannot be told apart from the
+/// one which was registered, with either poller.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A poller which incrementally updates the interests of an epoll instance.
+pub(crate) struct Poller {
+    /// The epoll instance's file descriptor.
+    epoll: File,
+
+    /// The interests which are currently registered on the epoll instance, and whether
+    /// they are edge-triggered.
+    registered: HashMap<RawFd, (Interest, bool)>,
+
+    /// Buffer which epoll writes ready events into.
+    buffer: Vec<libc::epoll_event>,
+
+    /// Events for file descriptors which epoll refused to register.
+    failed: Vec<(RawFd, Interest)>,
+
+    /// When the registered file descriptors were last checked for having been closed.
+    swept: Instant,
+
+    /// Signals to unblock while waiting, with `epoll_pwait` instead of `epoll_wait`.
+    signal_mask: Option<SignalMask>,
+}
+
+impl Poller {
+    pub fn new(interrupt: RawFd, signal_mask: Option<SignalMask>) -> io::Result<Self> {
+        let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let poller = Self {
+            epoll: unsafe { File::from_raw_fd(epoll) },
+            registered: HashMap::new(),
+            buffer: Vec::with_capacity(EVENTS_CAPACITY),
+            failed: Vec::new(),
+            swept: Instant::now(),
+            signal_mask,
+        };
+
+        poller.control(libc::EPOLL_CTL_ADD, interrupt, (Interest::READ, false))?;
+
+        Ok(poller)
+    }
+
+    /// Applies the changes to the registry since the last update to the epoll instance.
+    pub fn update(&mut self, registry: &mut Registry) {
+        for fd in registry.changed.drain() {
+            // Registrations with no interest are not polled, nor are deferred ones.
+            let deferred = registry.deferred.contains(&fd);
+            let interest = registry
+                .fds
+                .get(&fd)
+                .filter(|_| !deferred)
+                .map(|waiters| (merged_interest(waiters), edge_triggered(waiters)))
+                .filter(|(interest, _)| !interest.is_empty());
+
+            // The fd may have been closed and its number reused since it was last updated,
+            // even if its interest appears unchanged, in which case epoll no longer holds it.
+            // So every changed fd is modified, falling back to adding it, and vice versa.
+            let result = match (self.registered.get(&fd).copied(), interest) {
+                (None, Some(interest)) => {
+                    self.control(libc::EPOLL_CTL_ADD, fd, interest)
+                        .or_else(|why| match why.raw_os_error() {
+                            Some(libc::EEXIST) => self.control(libc::EPOLL_CTL_MOD, fd, interest),
+                            _ => Err(why),
+                        })
+                }
+                (Some(_), Some(interest)) => self
+                    .control(libc::EPOLL_CTL_MOD, fd, interest)
+                    .or_else(|why| match why.raw_os_error() {
+                        Some(libc::ENOENT) => self.control(libc::EPOLL_CTL_ADD, fd, interest),
+                        _ => Err(why),
+                    }),
+                (Some(_), None) => {
+                    // The fd may have already been closed, which removes it from epoll.
+                    let _ = self.control(libc::EPOLL_CTL_DEL, fd, (Interest::empty(), false));
+                    self.registered.remove(&fd);
+                    continue;
+                }
+                (None, None) => continue,
+            };
+
+            match result {
+                Ok(()) => {
+                    if let Some(interest) = interest {
+                        self.registered.insert(fd, interest);
+                    }
+                }
+                Err(why) => {
+                    self.registered.remove(&fd);
+                    let events = match why.raw_os_error() {
+                        Some(libc::EBADF) => Interest::INVALID,
+                        _ => Interest::ERROR,
+                    };
+                    self.failed.push((fd, events));
+                }
+            }
+        }
+    }
+
+    /// Blocks until at least one file descriptor is ready, or the timeout elapses, and
+    /// records which events occurred.
+    pub fn wait(
+        &mut self,
+        events: &mut Vec<(RawFd, Interest)>,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        // Report failed registrations without blocking, as epoll will never wake for them,
+        // and otherwise wake in time to sweep for closed file descriptors.
+        let timeout = if !self.failed.is_empty() {
+            0
+        } else if self.registered.is_empty() {
+            super::timeout_ms(timeout)
+        } else {
+            let sweep = (self.swept + SWEEP_INTERVAL).saturating_duration_since(Instant::now());
+            super::timeout_ms(Some(timeout.map_or(sweep, |timeout| timeout.min(sweep))))
+        };
+
+        let returned = unsafe {
+            match self.signal_mask {
+                Some(SignalMask(ref mask)) => libc::epoll_pwait(
+                    self.epoll.as_raw_fd(),
+                    self.buffer.as_mut_ptr(),
+                    EVENTS_CAPACITY as libc::c_int,
+                    timeout,
+                    mask,
+                ),
+                None => libc::epoll_wait(
+                    self.epoll.as_raw_fd(),
+                    self.buffer.as_mut_ptr(),
+                    EVENTS_CAPACITY as libc::c_int,
+                    timeout,
+                ),
+            }
+        };
+
+        if returned == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        unsafe { self.buffer.set_len(returned as usize) };
+
+        events.append(&mut self.failed);
+        events.extend(
+            self.buffer
+                .iter()
+                .map(|event| (event.u64 as RawFd, from_epoll(event.events))),
+        );
+
+        if !self.registered.is_empty() && self.swept.elapsed() >= SWEEP_INTERVAL {
+            self.sweep(events);
+        }
+
+        Ok(())
+    }
+
+    /// Reports registered file descriptors which have since been closed as invalid, as
+    /// `poll(2)` would, so that the futures waiting on them are woken.
+    fn sweep(&mut self, events: &mut Vec<(RawFd, Interest)>) {
+        self.swept = Instant::now();
+
+        for &fd in self.registered.keys() {
+            if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1
+                && io::Error::last_os_error().raw_os_error() == Some(libc::EBADF)
+            {
+                events.push((fd, Interest::INVALID));
+            }
+        }
+    }
+
+    fn control(
+        &self,
+        op: libc::c_int,
+        fd: RawFd,
+        (interest, edge): (Interest, bool),
+    ) -> io::Result<()> {
+        let mut events = to_epoll(interest);
+        if edge {
+            events |= libc::EPOLLET as u32;
+        }
+
+        let mut event = libc::epoll_event {
+            events,
+            u64: fd as u64,
+        };
+
+        if unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &mut event) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+fn to_epoll(interest: Interest) -> u32 {
+    let mut events = 0;
+
+    if interest.contains(Interest::READ) {
+        events |= libc::EPOLLIN;
+    }
+
+    if interest.contains(Interest::WRITE) {
+        events |= libc::EPOLLOUT;
+    }
+
+    if interest.contains(Interest::PRIORITY) {
+        events |= libc::EPOLLPRI;
+    }
+
+    if interest.contains(Interest::READ_HANGUP) {
+        events |= libc::EPOLLRDHUP;
+    }
+
+    events as u32
+}
+
+fn from_epoll(events: u32) -> Interest {
+    let events = events as libc::c_int;
+    let mut interest = Interest::empty();
+
+    if events & libc::EPOLLIN != 0 {
+        interest |= Interest::READ;
+    }
+
+    if events & libc::EPOLLOUT != 0 {
+        interest |= Interest::WRITE;
+    }
+
+    if events & libc::EPOLLPRI != 0 {
+        interest |= Interest::PRIORITY;
+    }
+
+    if events & libc::EPOLLRDHUP != 0 {
+        interest |= Interest::READ_HANGUP;
+    }
+
+    if events & libc::EPOLLHUP != 0 {
+        interest |= Interest::HANGUP;
+    }
+
+    if events & libc::EPOLLERR != 0 {
+        interest |= Interest::ERROR;
+    }
+
+    interest
+}
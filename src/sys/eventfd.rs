@@ -0,0 +1,66 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+};
+
+/// Interrupts the poller by incrementing the counter of an eventfd.
+///
+/// Unlike a pipe, this requires only a single file descriptor, and every pending
+/// notification is consumed by a single read of the counter.
+pub(crate) struct Interrupt {
+    eventfd: File,
+}
+
+impl Interrupt {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            eventfd: unsafe { File::from_raw_fd(fd) },
+        })
+    }
+
+    /// Replaces the eventfd by a new one under the same file descriptor, such as within a
+    /// child process, which shares the original with its parent.
+    pub fn reopen(&self) -> io::Result<()> {
+        let new = Self::new()?;
+        super::replace_fd(new.eventfd.as_raw_fd(), self.eventfd.as_raw_fd())
+    }
+
+    /// Wakes the poller which is listening on this interrupt.
+    pub fn notify(&self) -> io::Result<()> {
+        // Writing fails with `WouldBlock` if the counter would overflow, in which case the
+        // poller has a wakeup pending already.
+        match (&self.eventfd).write(&1u64.to_ne_bytes()) {
+            Err(why) if why.kind() != io::ErrorKind::WouldBlock => Err(why),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the file descriptor belongs to this interrupt.
+    pub fn contains(&self, fd: RawFd) -> bool {
+        fd == self.eventfd.as_raw_fd()
+    }
+
+    /// Consumes every pending notification.
+    pub fn drain(&self) {
+        let mut counter = [0u8; 8];
+
+        // A single read resets the counter, unless it was interrupted by a signal.
+        while let Err(why) = (&self.eventfd).read(&mut counter) {
+            if why.kind() != io::ErrorKind::Interrupted {
+                break;
+            }
+        }
+    }
+}
+
+impl AsRawFd for Interrupt {
+    fn as_raw_fd(&self) -> RawFd {
+        self.eventfd.as_raw_fd()
+    }
+}
@@ -0,0 +1,265 @@
+use super::SignalMask;
+use crate::{
+    reactor::{edge_triggered, merged_interest, Registry},
+    Interest,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io, mem,
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    ptr,
+    time::{Duration, Instant},
+};
+
+/// The maximum number of events which are received from a single call to `kevent(2)`.
+const EVENTS_CAPACITY: usize = 1024;
+
+/// How often the registered file descriptors are checked for having been closed.
+///
+/// Closing a file descriptor deletes its filters from the kqueue, which then never reports
+/// it, and would leave its registrations waiting forever, whereas `poll(2)` reports it as
+/// invalid.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A poller which incrementally updates the filters of a kqueue.
+pub(crate) struct Poller {
+    /// The kqueue's file descriptor.
+    kqueue: File,
+
+    /// The interests which are currently registered on the kqueue, and whether their
+    /// filters were added with `EV_CLEAR`.
+    registered: HashMap<RawFd, (Interest, bool)>,
+
+    /// Buffer which kqueue writes ready events into.
+    buffer: Vec<libc::kevent>,
+
+    /// Events for file descriptors which kqueue refused to register.
+    failed: Vec<(RawFd, Interest)>,
+
+    /// When the registered file descriptors were last checked for having been closed.
+    swept: Instant,
+}
+
+// The udata pointers of the buffered events are never set, nor dereferenced.
+unsafe impl Send for Poller {}
+
+impl Poller {
+    pub fn new(interrupt: RawFd, signal_mask: Option<SignalMask>) -> io::Result<Self> {
+        if signal_mask.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "kqueue is unable to wait with a signal mask",
+            ));
+        }
+
+        let kqueue = unsafe { libc::kqueue() };
+        if kqueue == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let kqueue = unsafe { File::from_raw_fd(kqueue) };
+
+        if unsafe { libc::fcntl(kqueue.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let poller = Self {
+            kqueue,
+            registered: HashMap::new(),
+            buffer: Vec::with_capacity(EVENTS_CAPACITY),
+            failed: Vec::new(),
+            swept: Instant::now(),
+        };
+
+        poller.control(interrupt, Interest::READ, Some(false))?;
+
+        Ok(poller)
+    }
+
+    /// Applies the changes to the registry since the last update to the kqueue.
+    pub fn update(&mut self, registry: &mut Registry) {
+        for fd in registry.changed.drain() {
+            let (mut old, was_edge) = self
+                .registered
+                .remove(&fd)
+                .unwrap_or((Interest::empty(), false));
+
+            // Deferred file descriptors are not polled.
+            let deferred = registry.deferred.contains(&fd);
+            let (new, edge) = registry
+                .fds
+                .get(&fd)
+                .filter(|_| !deferred)
+                .map_or((Interest::empty(), false), |waiters| {
+                    (merged_interest(waiters), edge_triggered(waiters))
+                });
+
+            // Adding an existing filter keeps the flags it was added with, so filters are
+            // deleted before being added again with or without `EV_CLEAR`.
+            if edge != was_edge && !new.is_empty() {
+                for &interest in &[Interest::READ, Interest::WRITE] {
+                    if old.contains(interest) {
+                        let _ = self.control(fd, interest, None);
+                    }
+                }
+
+                old = Interest::empty();
+            }
+
+            let mut result = Ok(());
+
+            for &interest in &[Interest::READ, Interest::WRITE] {
+                match (old.contains(interest), new.contains(interest)) {
+                    // Filters are added again even when unchanged, as the fd may have been
+                    // closed and its number reused since it was last updated, and adding an
+                    // existing filter has no effect.
+                    (_, true) => {
+                        result = result.and(self.control(fd, interest, Some(edge)));
+                    }
+                    (true, false) => {
+                        // The fd may have already been closed, which removes its filters.
+                        let _ = self.control(fd, interest, None);
+                    }
+                    _ => (),
+                }
+            }
+
+            match result {
+                Ok(()) => {
+                    if !new.is_empty() {
+                        self.registered.insert(fd, (new, edge));
+                    }
+                }
+                Err(why) => {
+                    for &interest in &[Interest::READ, Interest::WRITE] {
+                        let _ = self.control(fd, interest, None);
+                    }
+
+                    let events = match why.raw_os_error() {
+                        Some(libc::EBADF) => Interest::INVALID,
+                        _ => Interest::ERROR,
+                    };
+
+                    self.failed.push((fd, events));
+                }
+            }
+        }
+    }
+
+    /// Blocks until at least one file descriptor is ready, or the timeout elapses, and
+    /// records which events occurred.
+    pub fn wait(
+        &mut self,
+        events: &mut Vec<(RawFd, Interest)>,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        // Report failed registrations without blocking, as kqueue will never wake for them,
+        // and otherwise wake in time to sweep for closed file descriptors.
+        let timeout = if !self.failed.is_empty() {
+            Some(Duration::from_secs(0))
+        } else if self.registered.is_empty() {
+            timeout
+        } else {
+            let sweep = (self.swept + SWEEP_INTERVAL).saturating_duration_since(Instant::now());
+            Some(timeout.map_or(sweep, |timeout| timeout.min(sweep)))
+        };
+
+        let timespec = super::timespec(timeout);
+
+        let returned = unsafe {
+            libc::kevent(
+                self.kqueue.as_raw_fd(),
+                ptr::null(),
+                0,
+                self.buffer.as_mut_ptr(),
+                EVENTS_CAPACITY as _,
+                timespec.as_ref().map_or(ptr::null(), |timespec| timespec),
+            )
+        };
+
+        if returned == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        unsafe { self.buffer.set_len(returned as usize) };
+
+        events.append(&mut self.failed);
+        events.extend(self.buffer.iter().map(|event| {
+            let eof = event.flags & libc::EV_EOF != 0;
+            let mut interest = if event.filter == libc::EVFILT_WRITE {
+                Interest::WRITE
+            } else if eof && event.data == 0 {
+                // Reported as `poll(2)` does on Linux, which is only readable at the end of
+                // the file while data remains to be read.
+                Interest::empty()
+            } else {
+                Interest::READ
+            };
+
+            if eof {
+                interest |= Interest::HANGUP;
+            }
+
+            if event.flags & libc::EV_ERROR != 0 {
+                interest |= Interest::ERROR;
+            }
+
+            (event.ident as RawFd, interest)
+        }));
+
+        if !self.registered.is_empty() && self.swept.elapsed() >= SWEEP_INTERVAL {
+            self.sweep(events);
+        }
+
+        Ok(())
+    }
+
+    /// Reports registered file descriptors which have since been closed as invalid, as
+    /// `poll(2)` would, so that the futures waiting on them are woken.
+    fn sweep(&mut self, events: &mut Vec<(RawFd, Interest)>) {
+        self.swept = Instant::now();
+
+        for &fd in self.registered.keys() {
+            if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1
+                && io::Error::last_os_error().raw_os_error() == Some(libc::EBADF)
+            {
+                events.push((fd, Interest::INVALID));
+            }
+        }
+    }
+
+    /// Adds the read or write filter of a file descriptor, with `EV_CLEAR` if it is to be
+    /// edge-triggered, or deletes it if `None`.
+    fn control(&self, fd: RawFd, interest: Interest, add: Option<bool>) -> io::Result<()> {
+        let mut change: libc::kevent = unsafe { mem::zeroed() };
+        change.ident = fd as _;
+        change.filter = if interest.is_writable() {
+            libc::EVFILT_WRITE
+        } else {
+            libc::EVFILT_READ
+        };
+        change.flags = match add {
+            Some(true) => libc::EV_ADD | libc::EV_CLEAR,
+            Some(false) => libc::EV_ADD,
+            None => libc::EV_DELETE,
+        };
+
+        let returned = unsafe {
+            libc::kevent(
+                self.kqueue.as_raw_fd(),
+                &change,
+                1,
+                ptr::null_mut(),
+                0,
+                ptr::null(),
+            )
+        };
+
+        if returned == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Platform-specific implementations of the poller which the reactor's thread waits on, and
+//! of the interrupt which wakes it.
+//!
+//! Each poller listens for reads on the interrupt file descriptor, in addition to the
+//! interests of every file descriptor in the registry. On Linux, the interrupt is an
+//! eventfd, and elsewhere it is a pipe.
+
+// Both pollers are tested on Linux, whichever the reactor uses there.
+#[cfg(any(
+    all(target_os = "linux", feature = "epoll"),
+    all(test, target_os = "linux")
+))]
+mod epoll;
+
+#[cfg(all(target_os = "linux", feature = "epoll"))]
+pub(crate) use self::epoll::Poller;
+
+#[cfg(all(
+    any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ),
+    feature = "kqueue"
+))]
+mod kqueue;
+
+#[cfg(all(
+    any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ),
+    feature = "kqueue"
+))]
+pub(crate) use self::kqueue::Poller;
+
+#[cfg(any(
+    not(any(
+        all(target_os = "linux", feature = "epoll"),
+        all(
+            any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ),
+            feature = "kqueue"
+        )
+    )),
+    all(test, target_os = "linux")
+))]
+mod poll;
+
+#[cfg(not(any(
+    all(target_os = "linux", feature = "epoll"),
+    all(
+        any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ),
+        feature = "kqueue"
+    )
+)))]
+pub(crate) use self::poll::Poller;
+
+#[cfg(target_os = "linux")]
+mod eventfd;
+
+#[cfg(target_os = "linux")]
+pub(crate) use self::eventfd::Interrupt;
+
+#[cfg(not(target_os = "linux"))]
+mod pipe;
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) use self::pipe::Interrupt;
+
+/// Signals which are unblocked only while the poller waits, as with `ppoll(2)`.
+#[derive(Clone, Copy)]
+pub(crate) struct SignalMask(
+    // Never read where the mask is unsupported.
+    #[allow(dead_code)] pub libc::sigset_t,
+);
+
+impl std::fmt::Debug for SignalMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("SignalMask")
+    }
+}
+
+/// Converts a timeout into a timespec, or `None` to wait indefinitely.
+#[allow(dead_code)]
+fn timespec(timeout: Option<std::time::Duration>) -> Option<libc::timespec> {
+    timeout.map(duration_timespec)
+}
+
+/// Converts a duration into a timespec, saturating at the largest representable time.
+#[allow(dead_code)]
+pub(crate) fn duration_timespec(duration: std::time::Duration) -> libc::timespec {
+    let mut timespec: libc::timespec = unsafe { std::mem::zeroed() };
+    timespec.tv_sec = duration.as_secs().min(libc::time_t::MAX as u64) as libc::time_t;
+    timespec.tv_nsec = duration.subsec_nanos() as _;
+    timespec
+}
+
+/// Converts a timeout into milliseconds, rounding up so that deadlines are never missed.
+fn timeout_ms(timeout: Option<std::time::Duration>) -> libc::c_int {
+    match timeout {
+        Some(timeout) => {
+            let ms = timeout.as_nanos().div_ceil(1_000_000);
+            ms.min(libc::c_int::MAX as u128) as libc::c_int
+        }
+        None => -1,
+    }
+}
+
+/// The pending error of a socket, or `None` if the file descriptor is not a socket, or has
+/// no pending error.
+pub(crate) fn socket_error(fd: std::os::unix::io::RawFd) -> Option<std::io::Error> {
+    let mut errno: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+    let returned = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_ERROR,
+            &mut errno as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if returned == -1 || errno == 0 {
+        return None;
+    }
+
+    Some(std::io::Error::from_raw_os_error(errno))
+}
+
+/// Whether the file descriptor is open and in blocking mode, without `O_NONBLOCK` set.
+pub(crate) fn is_blocking(fd: std::os::unix::io::RawFd) -> bool {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    flags != -1 && flags & libc::O_NONBLOCK == 0
+}
+
+/// Whether the file descriptor is open and refers to a regular file.
+pub(crate) fn is_regular_file(fd: std::os::unix::io::RawFd) -> bool {
+    let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+
+    // `fstat` initializes the buffer whenever it succeeds.
+    let returned = unsafe { libc::fstat(fd, stat.as_mut_ptr()) };
+    returned == 0 && unsafe { stat.assume_init() }.st_mode & libc::S_IFMT == libc::S_IFREG
+}
+
+/// Polls a single file descriptor without waiting, returning the events which are ready.
+pub(crate) fn poll_now(
+    fd: std::os::unix::io::RawFd,
+    interest: crate::Interest,
+) -> std::io::Result<crate::Interest> {
+    let mut pollfd = [libc::pollfd {
+        fd,
+        events: interest.to_events(),
+        revents: 0,
+    }];
+
+    loop {
+        match poll_fds(&mut pollfd, Some(std::time::Duration::ZERO)) {
+            Ok(_) => return Ok(crate::Interest::from_revents(pollfd[0].revents)),
+            Err(why) if why.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(why) => return Err(why),
+        }
+    }
+}
+
+/// Identifies the file which the file descriptor refers to by its device and inode, or
+/// `None` if the file descriptor is not open.
+pub(crate) fn file_id(fd: std::os::unix::io::RawFd) -> Option<(u64, u64)> {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } == -1 {
+        return None;
+    }
+
+    Some((stat.st_dev as u64, stat.st_ino as u64))
+}
+
+/// Duplicates the file descriptor onto another number, which then refers to the same file,
+/// closing whichever file the number referred to before, and marking it close-on-exec.
+pub(crate) fn replace_fd(
+    fd: std::os::unix::io::RawFd,
+    onto: std::os::unix::io::RawFd,
+) -> std::io::Result<()> {
+    unsafe {
+        if libc::dup2(fd, onto) == -1 || libc::fcntl(onto, libc::F_SETFD, libc::FD_CLOEXEC) == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+extern "C" {
+    // Declared here, as the bindings of some platforms lack it.
+    fn pthread_atfork(
+        prepare: Option<extern "C" fn()>,
+        parent: Option<extern "C" fn()>,
+        child: Option<extern "C" fn()>,
+    ) -> libc::c_int;
+}
+
+/// Registers handlers which the forking thread calls before `fork(2)`, and afterwards
+/// within the parent and the child.
+pub(crate) fn at_fork(
+    prepare: extern "C" fn(),
+    parent: extern "C" fn(),
+    child: extern "C" fn(),
+) -> std::io::Result<()> {
+    match unsafe { pthread_atfork(Some(prepare), Some(parent), Some(child)) } {
+        0 => Ok(()),
+        errno => Err(std::io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// Polls the file descriptors until any is ready, or the timeout elapses, returning how
+/// many of them have events in their `revents`.
+pub(crate) fn poll_fds(
+    fds: &mut [libc::pollfd],
+    timeout: Option<std::time::Duration>,
+) -> std::io::Result<usize> {
+    // The slice is a contiguous array of initialized `pollfd`s, which poll only writes
+    // `revents` into, and which is borrowed mutably for the duration of the call.
+    let returned = unsafe {
+        libc::poll(
+            fds.as_mut_ptr(),
+            fds.len() as libc::nfds_t,
+            timeout_ms(timeout),
+        )
+    };
+
+    if returned == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(returned as usize)
+}
+
+// The pollers are tested on Linux, and wherever the reactor uses kqueue.
+#[cfg(all(
+    test,
+    any(
+        target_os = "linux",
+        all(
+            any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ),
+            feature = "kqueue"
+        )
+    )
+))]
+mod tests;
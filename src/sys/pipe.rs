@@ -0,0 +1,142 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+};
+
+/// Interrupts the poller by writing to a pipe which it listens for reads on.
+pub(crate) struct Interrupt {
+    reader: File,
+    writer: File,
+}
+
+impl Interrupt {
+    pub fn new() -> io::Result<Self> {
+        let (reader, writer) = create_pipe()?;
+        Ok(Self { reader, writer })
+    }
+
+    /// Replaces the pipe by a new one under the same file descriptors, such as within a
+    /// child process, which shares the original with its parent.
+    pub fn reopen(&self) -> io::Result<()> {
+        let new = Self::new()?;
+        super::replace_fd(new.reader.as_raw_fd(), self.reader.as_raw_fd())?;
+        super::replace_fd(new.writer.as_raw_fd(), self.writer.as_raw_fd())
+    }
+
+    /// Wakes the poller which is listening on this interrupt.
+    pub fn notify(&self) -> io::Result<()> {
+        // The pipe is non-blocking, and a full pipe already has a wakeup pending for the
+        // reactor, so a write failing with `WouldBlock` may be ignored. Writing through a
+        // shared reference avoids duplicating the file descriptor on every notification.
+        match (&self.writer).write(b"0") {
+            Err(why) if why.kind() != io::ErrorKind::WouldBlock => Err(why),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the file descriptor belongs to this interrupt.
+    pub fn contains(&self, fd: RawFd) -> bool {
+        fd == self.reader.as_raw_fd() || fd == self.writer.as_raw_fd()
+    }
+
+    /// Consumes every pending notification.
+    pub fn drain(&self) {
+        let mut buffer = [0u8; 64];
+
+        // Drain every pending interrupt, as many may have been written since the reactor
+        // last woke, until the non-blocking pipe would block.
+        loop {
+            match (&self.reader).read(&mut buffer) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(why) if why.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl AsRawFd for Interrupt {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.as_raw_fd()
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "illumos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "solaris"
+))]
+fn create_pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+
+    // Registering must never block on a full pipe, and draining must stop once it is empty.
+    // The pipe must also not leak into child processes.
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let reader = unsafe { File::from_raw_fd(fds[0]) };
+    let writer = unsafe { File::from_raw_fd(fds[1]) };
+    Ok((reader, writer))
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "illumos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "solaris"
+)))]
+fn create_pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let reader = unsafe { File::from_raw_fd(fds[0]) };
+    let writer = unsafe { File::from_raw_fd(fds[1]) };
+
+    // Without `pipe2`, the flags are set afterwards, which leaves a window in which a
+    // concurrent fork may inherit the pipe.
+    for file in &[&reader, &writer] {
+        set_flags(file.as_raw_fd())?;
+    }
+
+    Ok((reader, writer))
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "illumos",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "solaris"
+)))]
+fn set_flags(fd: RawFd) -> io::Result<()> {
+    unsafe {
+        // Registering must never block on a full pipe, and draining must stop once it is empty.
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // The pipe must also not leak into child processes.
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
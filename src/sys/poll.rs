@@ -0,0 +1,206 @@
+use super::SignalMask;
+use crate::{
+    reactor::{merged_interest, Registry},
+    Interest,
+};
+use std::{io, os::unix::io::RawFd, time::Duration};
+
+// Fails to compile should the arguments passed to poll not match its signature on a platform.
+const _: unsafe extern "C" fn(*mut libc::pollfd, libc::nfds_t, libc::c_int) -> libc::c_int =
+    libc::poll;
+
+/// A poller which rebuilds its set of file descriptors for each call to `poll(2)`.
+pub(crate) struct Poller {
+    pollers: Vec<libc::pollfd>,
+
+    /// Signals to unblock while waiting, with `ppoll` instead of `poll`.
+    signal_mask: Option<SignalMask>,
+}
+
+impl Poller {
+    pub fn new(interrupt: RawFd, signal_mask: Option<SignalMask>) -> io::Result<Self> {
+        #[cfg(not(any(
+            target_os = "android",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "linux",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        )))]
+        if signal_mask.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "ppoll is not available on this platform",
+            ));
+        }
+
+        let pollers = vec![libc::pollfd {
+            fd: interrupt,
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+
+        Ok(Self {
+            pollers,
+            signal_mask,
+        })
+    }
+
+    /// Synchronizes the set of polled file descriptors with the registry.
+    ///
+    /// The set is only rebuilt when registrations changed since the last update, and is
+    /// otherwise polled again as it is.
+    pub fn update(&mut self, registry: &mut Registry) {
+        if registry.changed.is_empty() {
+            self.pollers
+                .iter_mut()
+                .for_each(|poller| poller.revents = 0);
+            return;
+        }
+
+        registry.changed.clear();
+
+        self.pollers.truncate(1);
+
+        for (&fd, waiters) in registry.fds.iter() {
+            let interest = merged_interest(waiters);
+
+            // Registrations with no interest are not polled, nor are deferred ones.
+            if interest.is_empty() || registry.deferred.contains(&fd) {
+                continue;
+            }
+
+            self.pollers.push(libc::pollfd {
+                fd,
+                events: interest.to_events(),
+                revents: 0,
+            });
+        }
+    }
+
+    /// Blocks until at least one file descriptor is ready, or the timeout elapses, and
+    /// records which events occurred.
+    pub fn wait(
+        &mut self,
+        events: &mut Vec<(RawFd, Interest)>,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        let returned = match self.signal_mask {
+            Some(mask) => self.ppoll(timeout, mask),
+            None => super::poll_fds(&mut self.pollers, timeout),
+        };
+
+        if let Err(why) = returned {
+            // Polling more file descriptors than the process may open fails with `EINVAL`,
+            // which would otherwise give no hint as to its cause.
+            if why.raw_os_error() == Some(libc::EINVAL) && self.exceeds_fd_limit() {
+                return Err(io::Error::other(format!(
+                    "polling {} file descriptors exceeds the RLIMIT_NOFILE limit of open file \
+                     descriptors, which must be raised to register more",
+                    self.pollers.len()
+                )));
+            }
+
+            return Err(why);
+        }
+
+        events.extend(
+            self.pollers
+                .iter()
+                .filter(|event| event.revents != 0)
+                .map(|event| (event.fd, Interest::from_revents(event.revents))),
+        );
+
+        Ok(())
+    }
+
+    /// Whether more file descriptors are polled than the soft `RLIMIT_NOFILE` allows.
+    fn exceeds_fd_limit(&self) -> bool {
+        let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == -1 {
+            return false;
+        }
+
+        limit.rlim_cur != libc::RLIM_INFINITY && self.pollers.len() as u64 > limit.rlim_cur as u64
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "linux",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    fn ppoll(&mut self, timeout: Option<Duration>, mask: SignalMask) -> io::Result<usize> {
+        let timespec = super::timespec(timeout);
+
+        // As with `poll_fds`, the vector is borrowed mutably for the duration of the call.
+        let returned = unsafe {
+            libc::ppoll(
+                self.pollers.as_mut_ptr(),
+                self.pollers.len() as libc::nfds_t,
+                timespec
+                    .as_ref()
+                    .map_or(std::ptr::null(), |timespec| timespec),
+                &mask.0,
+            )
+        };
+
+        if returned == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(returned as usize)
+    }
+
+    #[cfg(not(any(
+        target_os = "android",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "linux",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    fn ppoll(&mut self, _timeout: Option<Duration>, _mask: SignalMask) -> io::Result<usize> {
+        unreachable!("pollers are never created with a signal mask on this platform")
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use std::os::unix::io::AsRawFd;
+
+    /// Polling more file descriptors than the process may open fails with an error which
+    /// names the limit, rather than with a bare `EINVAL`.
+    #[test]
+    fn exceeding_the_fd_limit_names_it() {
+        if !in_child_process("sys::poll::tests::exceeding_the_fd_limit_names_it") {
+            return;
+        }
+
+        let (interrupt, _notify) = pipe();
+        let pipes = (0..32).map(|_| pipe()).collect::<Vec<_>>();
+        let interests = pipes
+            .iter()
+            .map(|(reader, _)| (reader.as_raw_fd(), Interest::READ))
+            .collect::<Vec<_>>();
+
+        let mut poller = Poller::new(interrupt.as_raw_fd(), None).unwrap();
+        poller.update(&mut Registry::with_interests(&interests));
+
+        let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+        assert_eq!(
+            unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) },
+            0
+        );
+        limit.rlim_cur = 16;
+        assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) }, 0);
+
+        let why = poller.wait(&mut Vec::new(), Some(TIMEOUT)).unwrap_err();
+        assert_eq!(why.raw_os_error(), None);
+        assert!(why.to_string().contains("RLIMIT_NOFILE"), "{}", why);
+    }
+}
@@ -0,0 +1,254 @@
+//! Scenarios which every poller is expected to report identically, run against each poller
+//! which is available on the platform: poll and epoll on Linux, and kqueue elsewhere, and
+//! the tests of the `poll(2)` wrapper which the pollers share.
+
+use crate::{reactor::Registry, test_util::*, Interest};
+use std::{
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    time::Duration,
+};
+
+/// The events which the poller reports for the file descriptors after a single wait, with
+/// the interrupt's events omitted, optionally running a closure once they were registered.
+///
+/// Events which kqueue reports separately for each filter of a file descriptor are merged.
+macro_rules! poll_once {
+    ($poller:ident, $interests:expr $(, $registered:expr)?) => {{
+        let (interrupt, _notify) = pipe();
+        let mut poller = crate::sys::$poller::Poller::new(interrupt.as_raw_fd(), None).unwrap();
+        let mut registry = Registry::with_interests($interests);
+        poller.update(&mut registry);
+        $($registered();)?
+
+        let mut events = Vec::new();
+        poller.wait(&mut events, Some(TIMEOUT)).unwrap();
+        events.retain(|&(fd, _)| fd != interrupt.as_raw_fd());
+        merged(events)
+    }};
+}
+
+/// The events which the poller reports for the file descriptors on a second wait, after
+/// the first wait reported the writable writer, and the closure changed the registry.
+macro_rules! poll_twice {
+    ($poller:ident, $interests:expr, $between:expr) => {{
+        let (interrupt, _notify) = pipe();
+        let mut poller = crate::sys::$poller::Poller::new(interrupt.as_raw_fd(), None).unwrap();
+        let mut registry = Registry::with_interests($interests);
+        poller.update(&mut registry);
+
+        let mut events = Vec::new();
+        poller.wait(&mut events, Some(TIMEOUT)).unwrap();
+        assert_eq!(events, [($interests[1].0, Interest::WRITE)]);
+
+        $between(&mut registry);
+        poller.update(&mut registry);
+        events.clear();
+        poller.wait(&mut events, Some(TIMEOUT)).unwrap();
+        events.retain(|&(fd, _)| fd != interrupt.as_raw_fd());
+        merged(events)
+    }};
+}
+
+macro_rules! parity_tests {
+    ($poller:ident) => {
+        mod $poller {
+            use super::*;
+
+            #[test]
+            fn readable() {
+                let (reader, writer) = pipe();
+                write_byte(&writer);
+
+                let events = poll_once!($poller, &[(reader.as_raw_fd(), Interest::READ)]);
+                assert_eq!(events, [(reader.as_raw_fd(), Interest::READ)]);
+            }
+
+            #[test]
+            fn writable() {
+                let (_reader, writer) = pipe();
+
+                let events = poll_once!($poller, &[(writer.as_raw_fd(), Interest::WRITE)]);
+                assert_eq!(events, [(writer.as_raw_fd(), Interest::WRITE)]);
+            }
+
+            #[test]
+            fn sockets_readable_and_writable() {
+                let (socket, peer) = socketpair();
+                write_byte(&peer);
+
+                let events = poll_once!($poller, &[(socket.as_raw_fd(), Interest::BOTH)]);
+                assert_eq!(events, [(socket.as_raw_fd(), Interest::BOTH)]);
+            }
+
+            #[test]
+            fn sockets_writable_only() {
+                let (socket, _peer) = socketpair();
+
+                let events = poll_once!($poller, &[(socket.as_raw_fd(), Interest::BOTH)]);
+                assert_eq!(events, [(socket.as_raw_fd(), Interest::WRITE)]);
+            }
+
+            #[test]
+            fn not_ready() {
+                let (reader, writer) = pipe();
+
+                // The writer is ready, so that the wait returns.
+                let interests = &[
+                    (reader.as_raw_fd(), Interest::READ),
+                    (writer.as_raw_fd(), Interest::WRITE),
+                ];
+                let events = poll_once!($poller, interests);
+                assert_eq!(events, [(writer.as_raw_fd(), Interest::WRITE)]);
+            }
+
+            #[test]
+            fn hung_up() {
+                let (reader, writer) = pipe();
+                drop(writer);
+
+                let events = poll_once!($poller, &[(reader.as_raw_fd(), Interest::READ)]);
+                assert_eq!(events, [(reader.as_raw_fd(), Interest::HANGUP)]);
+            }
+
+            #[test]
+            fn closed() {
+                let (reader, _writer) = pipe();
+                let duplicate = high_duplicate(&reader);
+
+                // Closed after being registered, while its file remains open through the
+                // other file descriptor, so epoll keeps the file in its interest list, and
+                // kqueue deletes its filters without reporting it.
+                let interests = &[(duplicate, Interest::READ)];
+                let events = poll_once!($poller, interests, || drop(unsafe {
+                    OwnedFd::from_raw_fd(duplicate)
+                }));
+                assert_eq!(events, [(duplicate, Interest::INVALID)]);
+            }
+
+            #[test]
+            fn unchanged_between_waits() {
+                let (reader, writer) = pipe();
+                let interests = &[
+                    (reader.as_raw_fd(), Interest::READ),
+                    (writer.as_raw_fd(), Interest::WRITE),
+                ];
+
+                let mut events = poll_twice!($poller, interests, |_: &mut Registry| {
+                    write_byte(&writer)
+                });
+                events.sort_unstable_by_key(|&(fd, _)| fd);
+                assert_eq!(
+                    events,
+                    [
+                        (reader.as_raw_fd(), Interest::READ),
+                        (writer.as_raw_fd(), Interest::WRITE)
+                    ]
+                );
+            }
+
+            #[test]
+            fn changed_between_waits() {
+                let (reader, writer) = pipe();
+                let interests = &[
+                    (reader.as_raw_fd(), Interest::READ),
+                    (writer.as_raw_fd(), Interest::WRITE),
+                ];
+
+                let events = poll_twice!($poller, interests, |registry: &mut Registry| {
+                    registry.fds.remove(&writer.as_raw_fd());
+                    registry.changed.insert(writer.as_raw_fd());
+                    write_byte(&writer);
+                });
+                assert_eq!(events, [(reader.as_raw_fd(), Interest::READ)]);
+            }
+        }
+    };
+}
+
+#[cfg(target_os = "linux")]
+parity_tests!(poll);
+#[cfg(target_os = "linux")]
+parity_tests!(epoll);
+#[cfg(not(target_os = "linux"))]
+parity_tests!(kqueue);
+
+/// Merges the events which were reported for the same file descriptor, in the order in
+/// which each file descriptor was first reported.
+fn merged(events: Vec<(RawFd, Interest)>) -> Vec<(RawFd, Interest)> {
+    let mut merged: Vec<(RawFd, Interest)> = Vec::with_capacity(events.len());
+    for (fd, interest) in events {
+        match merged.iter_mut().find(|&&mut (merged, _)| merged == fd) {
+            Some((_, merged)) => *merged |= interest,
+            None => merged.push((fd, interest)),
+        }
+    }
+
+    merged
+}
+
+/// Duplicates the file descriptor onto a number far above those which the other tests
+/// open, so that it is not reused by them while it is closed.
+fn high_duplicate(fd: &impl AsRawFd) -> RawFd {
+    let duplicate = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 900) };
+    assert_ne!(duplicate, -1, "fcntl: {}", std::io::Error::last_os_error());
+    duplicate
+}
+
+/// The wrapper reports how many file descriptors have events, skipping negative ones, and
+/// reports closed file descriptors through their `revents`, rather than as an error.
+#[test]
+fn poll_fds_report_ready_file_descriptors() {
+    let pollfd = |fd: &dyn AsRawFd, events| libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events,
+        revents: 0,
+    };
+
+    let (reader, writer) = pipe();
+    assert_eq!(super::poll_fds(&mut [], Some(Duration::ZERO)).unwrap(), 0);
+
+    let mut fds = [pollfd(&reader, libc::POLLIN), pollfd(&-1, libc::POLLIN)];
+    assert_eq!(super::poll_fds(&mut fds, Some(Duration::ZERO)).unwrap(), 0);
+
+    write_byte(&writer);
+    fds[1] = pollfd(&writer, libc::POLLOUT);
+    assert_eq!(super::poll_fds(&mut fds, Some(TIMEOUT)).unwrap(), 2);
+    assert_eq!(
+        (fds[0].revents, fds[1].revents),
+        (libc::POLLIN, libc::POLLOUT)
+    );
+
+    let closed = high_duplicate(&reader);
+    drop(unsafe { OwnedFd::from_raw_fd(closed) });
+    let mut fds = [pollfd(&closed, libc::POLLIN)];
+    assert_eq!(super::poll_fds(&mut fds, None).unwrap(), 1);
+    assert_eq!(fds[0].revents, libc::POLLNVAL);
+}
+
+/// A signal which interrupts the wrapper's wait is returned as an error of kind
+/// [`io::ErrorKind::Interrupted`](std::io::ErrorKind::Interrupted), for its callers to
+/// retry.
+#[test]
+fn poll_fds_report_interruptions() {
+    if !in_child_process("sys::tests::poll_fds_report_interruptions") {
+        return;
+    }
+
+    ignore_signal(libc::SIGUSR2);
+    let (reader, _writer) = pipe();
+    let mut fds = [libc::pollfd {
+        fd: reader.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    }];
+
+    let waiting = unsafe { libc::pthread_self() } as usize;
+    let signal = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        unsafe { libc::pthread_kill(waiting as libc::pthread_t, libc::SIGUSR2) };
+    });
+
+    let why = super::poll_fds(&mut fds, Some(TIMEOUT)).unwrap_err();
+    assert_eq!(why.kind(), std::io::ErrorKind::Interrupted);
+    signal.join().unwrap();
+}
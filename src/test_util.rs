@@ -0,0 +1,261 @@
+//! Helpers which are shared by the tests of every module.
+
+use std::{
+    env,
+    future::Future,
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    pin::pin,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+/// How long a test waits on the reactor before it is considered to have hung.
+pub(crate) const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Creates a non-blocking pipe, returning its read and write ends.
+pub(crate) fn pipe() -> (OwnedFd, OwnedFd) {
+    let mut fds = [0; 2];
+    let returned = unsafe { libc::pipe(fds.as_mut_ptr()) };
+    assert_eq!(returned, 0, "pipe: {}", std::io::Error::last_os_error());
+
+    nonblocking(fds)
+}
+
+/// Creates a non-blocking pair of connected Unix stream sockets.
+pub(crate) fn socketpair() -> (OwnedFd, OwnedFd) {
+    let mut fds = [0; 2];
+    let returned =
+        unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+    assert_eq!(
+        returned,
+        0,
+        "socketpair: {}",
+        std::io::Error::last_os_error()
+    );
+
+    nonblocking(fds)
+}
+
+/// Takes ownership of both file descriptors, after setting them to non-blocking mode.
+fn nonblocking(fds: [RawFd; 2]) -> (OwnedFd, OwnedFd) {
+    for &fd in &fds {
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            assert_ne!(libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK), -1);
+            assert_ne!(libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC), -1);
+        }
+    }
+
+    unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+}
+
+/// Initiates a non-blocking connection to the port on the loopback address.
+pub(crate) fn connect(port: u16) -> OwnedFd {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    assert_ne!(fd, -1, "socket: {}", std::io::Error::last_os_error());
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    assert_ne!(
+        unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK) },
+        -1
+    );
+
+    let mut address: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    address.sin_family = libc::AF_INET as libc::sa_family_t;
+    address.sin_port = port.to_be();
+    address.sin_addr.s_addr = u32::from(std::net::Ipv4Addr::LOCALHOST).to_be();
+
+    let returned = unsafe {
+        libc::connect(
+            fd,
+            &address as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    };
+
+    // The connection may complete, or be refused, before `connect` even returns.
+    let why = std::io::Error::last_os_error();
+    assert!(
+        returned == 0 || why.raw_os_error() == Some(libc::EINPROGRESS),
+        "connect: {}",
+        why
+    );
+
+    socket
+}
+
+/// Writes a single byte to the file descriptor, which makes its peer readable.
+pub(crate) fn write_byte(fd: &impl AsRawFd) {
+    let written = unsafe { libc::write(fd.as_raw_fd(), b"0".as_ptr().cast(), 1) };
+    assert_eq!(written, 1, "write: {}", std::io::Error::last_os_error());
+}
+
+/// Reads a byte from the file descriptor, failing with `WouldBlock` once it is drained.
+pub(crate) fn read_byte(fd: &impl AsRawFd) -> std::io::Result<()> {
+    match unsafe { libc::read(fd.as_raw_fd(), [0u8; 1].as_mut_ptr().cast(), 1) } {
+        -1 => Err(std::io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Waits for the condition to hold, panicking if it has not within the [`TIMEOUT`].
+pub(crate) fn wait_until(condition: impl Fn() -> bool) {
+    let deadline = Instant::now() + TIMEOUT;
+    while !condition() {
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting for the condition"
+        );
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Unparks a thread when woken.
+pub(crate) struct Unparker(pub Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Counts how many times it was woken.
+#[derive(Default)]
+pub(crate) struct CountingWaker(pub AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// A waker which does nothing.
+pub(crate) fn noop_waker() -> Waker {
+    Waker::from(Arc::new(CountingWaker::default()))
+}
+
+/// Polls the future on the current thread until it completes, panicking if it has not
+/// completed within the [`TIMEOUT`].
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(Unparker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    let deadline = Instant::now() + TIMEOUT;
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+
+        let now = Instant::now();
+        assert!(now < deadline, "timed out waiting for the future");
+        thread::park_timeout(deadline - now);
+    }
+}
+
+/// Runs the function on another thread, panicking if it has not returned within the
+/// [`TIMEOUT`], such as when it deadlocks.
+pub(crate) fn within<R: Send + 'static>(f: impl FnOnce() -> R + Send + 'static) -> R {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+
+    match receiver.recv_timeout(TIMEOUT) {
+        Ok(output) => output,
+        Err(mpsc::RecvTimeoutError::Timeout) => panic!("timed out waiting for the thread"),
+        Err(mpsc::RecvTimeoutError::Disconnected) => panic!("the thread panicked"),
+    }
+}
+
+/// Set in the environment of a test which runs in a child process.
+const CHILD: &str = "FD_REACTOR_TEST_CHILD";
+
+/// Runs the named test again in a child process, returning whether the current process is
+/// that child, in which case the test goes on to run its body.
+///
+/// Tests which change process-wide state, such as resource limits, run in a child so that
+/// the tests running alongside them are unaffected.
+pub(crate) fn in_child_process(test: &str) -> bool {
+    in_child_process_blocking(test, &[])
+}
+
+/// Runs the test in a child process, as with [`in_child_process`], whose every thread
+/// blocks the given signals from the start, such that they are only ever queued.
+pub(crate) fn in_child_process_blocking(test: &str, signals: &[libc::c_int]) -> bool {
+    use std::os::unix::process::CommandExt;
+
+    if env::var_os(CHILD).is_some() {
+        return true;
+    }
+
+    let mut blocked: libc::sigset_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::sigemptyset(&mut blocked) };
+    for &signal in signals {
+        unsafe { libc::sigaddset(&mut blocked, signal) };
+    }
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command
+        .args([test, "--exact", "--test-threads=1"])
+        .env(CHILD, "1")
+        .stdout(Stdio::null());
+
+    // The mask is inherited across `exec`, and by every thread of the child, which the
+    // standard library would otherwise have reset by the time that this runs.
+    unsafe {
+        command.pre_exec(move || {
+            match libc::pthread_sigmask(libc::SIG_BLOCK, &blocked, std::ptr::null_mut()) {
+                0 => Ok(()),
+                error => Err(std::io::Error::from_raw_os_error(error)),
+            }
+        });
+    }
+
+    let status = command.status().unwrap();
+
+    assert!(
+        status.success(),
+        "{} failed in a child process: {}",
+        test,
+        status
+    );
+    false
+}
+
+/// Lowers the limit of open files to the number which are open, so that opening any more
+/// fails with `EMFILE`.
+pub(crate) fn exhaust_open_files() {
+    let lowest = unsafe { libc::dup(0) };
+    assert_ne!(lowest, -1, "dup: {}", std::io::Error::last_os_error());
+    unsafe { libc::close(lowest) };
+
+    let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) },
+        0
+    );
+    limit.rlim_cur = lowest as libc::rlim_t;
+    assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) }, 0);
+}
+
+/// Handles the signal with a handler which does nothing, without `SA_RESTART`, so that the
+/// signal interrupts whichever system call it arrives during.
+pub(crate) fn ignore_signal(signal: libc::c_int) {
+    extern "C" fn handler(_: libc::c_int) {}
+
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    let returned = unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) };
+    assert_eq!(
+        returned,
+        0,
+        "sigaction: {}",
+        std::io::Error::last_os_error()
+    );
+}
@@ -0,0 +1,177 @@
+use crate::{sys::duration_timespec, FdFuture, Interest};
+use std::{
+    io,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    time::Duration,
+};
+
+/// A timer which expires through a `timerfd(2)`, waiting on the global reactor for its
+/// expirations.
+///
+/// The timer measures time with `CLOCK_MONOTONIC`.
+pub struct TimerFd {
+    fd: OwnedFd,
+}
+
+impl TimerFd {
+    /// Creates a timer which is disarmed until it is next set.
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_CLOEXEC | libc::TFD_NONBLOCK,
+            )
+        };
+
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Creates a timer which expires once every period, starting one period from now.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the period is zero.
+    pub fn interval(period: Duration) -> io::Result<Self> {
+        if period.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the period of an interval must not be zero",
+            ));
+        }
+
+        let timer = Self::new()?;
+        timer.set(period, period)?;
+        Ok(timer)
+    }
+
+    /// Waits for the given duration, replacing any schedule which the timer had.
+    pub async fn sleep(&self, duration: Duration) -> io::Result<()> {
+        // Setting a timer to zero disarms it, so it would never expire.
+        if duration.is_zero() {
+            self.set(Duration::ZERO, Duration::ZERO)?;
+            return Ok(());
+        }
+
+        self.set(duration, Duration::ZERO)?;
+        self.tick().await.map(drop)
+    }
+
+    /// Waits for the timer to expire, returning how many times it has expired since it was
+    /// last waited on.
+    ///
+    /// Expirations of an interval which are missed are counted, rather than queued, so an
+    /// interval which is waited on late expires once more with a count greater than one.
+    pub async fn tick(&self) -> io::Result<u64> {
+        loop {
+            match self.try_tick() {
+                Err(why) if why.kind() == io::ErrorKind::WouldBlock => {
+                    FdFuture::from_fd(&self.fd, Interest::READ)
+                        .try_ready()
+                        .await?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Reads how many times the timer has expired, failing with `WouldBlock` if it has not
+    /// expired since it was last read.
+    pub fn try_tick(&self) -> io::Result<u64> {
+        let mut expirations = [0u8; 8];
+
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    expirations.as_mut_ptr() as *mut libc::c_void,
+                    expirations.len(),
+                )
+            };
+
+            if read == -1 {
+                let why = io::Error::last_os_error();
+                if why.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                return Err(why);
+            }
+
+            return Ok(u64::from_ne_bytes(expirations));
+        }
+    }
+
+    /// Arms the timer to first expire after `initial`, and then once every `period`, or
+    /// disarms it if `initial` is zero.
+    fn set(&self, initial: Duration, period: Duration) -> io::Result<()> {
+        let spec = libc::itimerspec {
+            it_interval: duration_timespec(period),
+            it_value: duration_timespec(initial),
+        };
+
+        let returned =
+            unsafe { libc::timerfd_settime(self.fd.as_raw_fd(), 0, &spec, std::ptr::null_mut()) };
+
+        if returned == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+impl AsFd for TimerFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for TimerFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use std::time::Instant;
+
+    /// Sleeping waits out the duration, after which the timer is disarmed.
+    #[test]
+    fn sleeps_expire_once() {
+        let timer = TimerFd::new().unwrap();
+        let started = Instant::now();
+
+        block_on(timer.sleep(Duration::from_millis(50))).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        let why = timer.try_tick().unwrap_err();
+        assert_eq!(why.kind(), io::ErrorKind::WouldBlock);
+        block_on(timer.sleep(Duration::ZERO)).unwrap();
+    }
+
+    /// Intervals expire once every period, and count the expirations which were missed.
+    #[test]
+    fn intervals_expire_every_period() {
+        let period = Duration::from_millis(20);
+        let timer = TimerFd::interval(period).unwrap();
+        let started = Instant::now();
+
+        for tick in 1..=3 {
+            assert!(block_on(timer.tick()).unwrap() >= 1);
+            assert!(started.elapsed() >= period * tick);
+        }
+
+        std::thread::sleep(period * 3);
+        assert!(timer.try_tick().unwrap() >= 2);
+
+        let why = TimerFd::interval(Duration::ZERO).map(drop).unwrap_err();
+        assert_eq!(why.kind(), io::ErrorKind::InvalidInput);
+    }
+}
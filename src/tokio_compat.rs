@@ -0,0 +1,177 @@
+//! Adapters for driving file descriptors from tokio's I/O traits with this reactor.
+
+use crate::{FdFuture, Interest, TryFdFuture};
+use std::{
+    future::Future,
+    io::{self, Read, Write},
+    os::unix::io::AsRawFd,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Implements tokio's `AsyncRead` and `AsyncWrite` for a non-blocking file descriptor,
+/// waiting on the global reactor rather than on tokio's.
+///
+/// This is for file descriptors which tokio does not otherwise support, and works with
+/// any runtime, since the reactor only relies on the waker of the task which polls it.
+pub struct TokioFd<T> {
+    inner: T,
+
+    /// Waits for the file descriptor to become readable, after a read would block.
+    read: Option<TryFdFuture<'static>>,
+
+    /// Waits for the file descriptor to become writable, after a write would block.
+    write: Option<TryFdFuture<'static>>,
+}
+
+impl<T: AsRawFd> TokioFd<T> {
+    /// Wraps a file descriptor which has been set to non-blocking mode.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            read: None,
+            write: None,
+        }
+    }
+
+    /// A reference to the wrapped value.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// A mutable reference to the wrapped value.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps the inner value.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// Performs an operation until it no longer would block, waiting on the future for the
+/// file descriptor to become ready in between.
+///
+/// A file descriptor which the reactor is unable to poll, or which has an error condition,
+/// fails with the error which that represents, rather than being retried, as it would
+/// otherwise be found ready again immediately.
+fn poll_io<R>(
+    fd: &impl AsRawFd,
+    waiting: &mut Option<TryFdFuture<'static>>,
+    interest: Interest,
+    cx: &mut Context,
+    mut op: impl FnMut() -> io::Result<R>,
+) -> Poll<io::Result<R>> {
+    loop {
+        if let Some(future) = waiting {
+            let result = match Pin::new(future).poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            };
+
+            *waiting = None;
+            if let Err(why) = result {
+                return Poll::Ready(Err(why));
+            }
+        }
+
+        match op() {
+            Err(why) if why.kind() == io::ErrorKind::WouldBlock => {
+                *waiting = Some(FdFuture::new(fd.as_raw_fd(), interest).try_ready());
+            }
+            result => return Poll::Ready(result),
+        }
+    }
+}
+
+impl<T: AsRawFd + Read + Unpin> AsyncRead for TokioFd<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let (inner, read) = (&mut this.inner, &mut this.read);
+        let fd = inner.as_raw_fd();
+
+        let read = poll_io(&fd, read, Interest::READ, cx, || {
+            inner.read(buf.initialize_unfilled())
+        });
+
+        read.map_ok(|read| buf.advance(read))
+    }
+}
+
+impl<T: AsRawFd + Write + Unpin> AsyncWrite for TokioFd<T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let (inner, write) = (&mut this.inner, &mut this.write);
+        let fd = inner.as_raw_fd();
+
+        poll_io(&fd, write, Interest::WRITE, cx, || inner.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let (inner, write) = (&mut this.inner, &mut this.write);
+        let fd = inner.as_raw_fd();
+
+        poll_io(&fd, write, Interest::WRITE, cx, || inner.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{readable, test_util::*, REACTOR};
+    use std::{fs::File, thread, time::Duration};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Futures of the reactor are woken within tokio's runtime, as with any other runtime.
+    #[tokio::test]
+    async fn futures_complete_within_tokio() {
+        let (reader, writer) = pipe();
+
+        let write = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            write_byte(&writer);
+            writer
+        });
+
+        let events = tokio::time::timeout(TIMEOUT, readable(reader.as_raw_fd())).await;
+        assert!(events.unwrap().is_readable());
+
+        let mut reader = TokioFd::new(File::from(reader));
+        let mut buffer = [0; 8];
+        let read = tokio::time::timeout(TIMEOUT, reader.read(&mut buffer)).await;
+        assert_eq!(read.unwrap().unwrap(), 1);
+
+        drop(write.join().unwrap());
+        let read = tokio::time::timeout(TIMEOUT, reader.read(&mut buffer)).await;
+        assert_eq!(read.unwrap().unwrap(), 0);
+    }
+
+    /// An error condition fails the write which waits on it, rather than being retried.
+    #[tokio::test]
+    async fn error_conditions_fail_io() {
+        let (reader, writer) = pipe();
+        let fd = writer.as_raw_fd();
+        let mut writer = TokioFd::new(File::from(writer));
+
+        let write = tokio::spawn(async move { writer.write_all(&[0; 1 << 20]).await });
+        while !REACTOR.is_registered(fd) {
+            tokio::task::yield_now().await;
+        }
+
+        // Closing the read end of a full pipe is an error condition of its write end.
+        drop(reader);
+        let write = tokio::time::timeout(TIMEOUT, write).await.unwrap().unwrap();
+        assert!(write.is_err());
+    }
+}